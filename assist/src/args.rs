@@ -20,6 +20,9 @@ pub enum AssistCommand {
         /// The path to the JSON file containing the benchmark results.
         #[structopt(parse(from_os_str))]
         results: Option<PathBuf>,
+        /// Extra percentiles to compute from the recorded histograms (e.g. 99.99,99.999).
+        #[structopt(long, use_delimiter = true)]
+        quantiles: Vec<f64>,
     },
     /// ## Compare
     ///
@@ -33,9 +36,13 @@ pub enum AssistCommand {
         /// A flag to suppress the output if no regression is detected.
         #[structopt(long)]
         silent: bool,
-        /// The sensitivity threshold for detecting performance regressions (0-100).
+        /// The sensitivity threshold for detecting performance regressions (0-100), in
+        /// percent of the baseline, or in percentage points for the error rates.
         #[structopt(long)]
         sensitivity: u8,
+        /// Extra percentiles to compare, computed from the recorded histograms (e.g. 99.99).
+        #[structopt(long, use_delimiter = true)]
+        quantiles: Vec<f64>,
     },
     /// ## Cleanup
     ///
@@ -1,5 +1,5 @@
 use core::{
-    stats::{BenchStatistics, ObservationsStats},
+    stats::{BenchStatistics, ErrorStats, ObservationsStats},
     types::BenchResult,
 };
 use std::{fs, path::PathBuf};
@@ -21,12 +21,13 @@ pub fn compare(
     that: Option<PathBuf>,
     sensitivity: u8,
     silent: bool,
+    quantiles: Vec<f64>,
 ) -> BenchResult<()> {
     let sensitivity = sensitivity as f64;
     let this_path = this.unwrap_or_else(|| latest_run_output_path(1));
     let that_path = that.unwrap_or_else(|| latest_run_output_path(2));
 
-    let this: BenchStatistics = json::from_str(&fs::read_to_string(this_path)?)?;
+    let mut this: BenchStatistics = json::from_str(&fs::read_to_string(this_path)?)?;
     let mut that: BenchStatistics = json::from_str(&fs::read_to_string(that_path)?)?;
 
     let mut table = Table::new();
@@ -34,17 +35,30 @@ pub fn compare(
 
     for (mode, this_stats) in this.request_stats {
         if let Some(that_stats) = that.request_stats.remove(&mode) {
-            let metrics = vec![("Request Latency (μs)", this_stats, that_stats, 1.0)];
+            let mut metrics = vec![("Request Latency (μs)", this_stats, that_stats, 1.0)];
+            let this_corrected = this.corrected_request_stats.remove(&mode);
+            let that_corrected = that.corrected_request_stats.remove(&mode);
+            if let (Some(this_corrected), Some(that_corrected)) = (this_corrected, that_corrected) {
+                metrics.push((
+                    "Request Latency, CO (μs)",
+                    this_corrected,
+                    that_corrected,
+                    1.0,
+                ));
+            }
+            let errors = this.errors.get(&mode).zip(that.errors.get(&mode));
             add_metrics_to_table(
                 &mut table,
                 &mode,
+                errors,
                 metrics,
                 sensitivity,
+                &quantiles,
                 &mut regression_detected,
             );
         }
     }
-    let metrics = vec![
+    let mut metrics = vec![
         (
             "Sig. Confirm Latency (μs)",
             this.signature_confirmation_latency,
@@ -57,13 +71,35 @@ pub fn compare(
             that.account_update_latency,
             1.0,
         ),
-        ("TPS", this.rps, that.rps, -1.0),
     ];
+    // Corrected latencies are missing from the results of older versions
+    let corrected = [
+        (
+            "Sig. Confirm Latency, CO (μs)",
+            this.corrected_signature_confirmation_latency,
+            that.corrected_signature_confirmation_latency,
+            1.0,
+        ),
+        (
+            "Acct. Update Latency, CO (μs)",
+            this.corrected_account_update_latency,
+            that.corrected_account_update_latency,
+            1.0,
+        ),
+    ];
+    metrics.extend(
+        corrected
+            .into_iter()
+            .filter(|(_, this, that, _)| this.count > 0 && that.count > 0),
+    );
+    metrics.push(("TPS", this.rps, that.rps, -1.0));
     add_metrics_to_table(
         &mut table,
         "",
+        None,
         metrics,
         sensitivity,
+        &quantiles,
         &mut regression_detected,
     );
 
@@ -80,37 +116,62 @@ pub fn compare(
 
 /// # Add Metrics to Table
 ///
-/// A helper function to add a set of metrics to the comparison table.
+/// A helper function to add a set of metrics to the comparison table, preceded by the
+/// error rate of the mode, if both runs recorded one. Extra `quantiles` (in percent)
+/// are recomputed from the histograms, when both runs have them.
 fn add_metrics_to_table(
     table: &mut Table,
     mode: &str,
+    errors: Option<(&ErrorStats, &ErrorStats)>,
     metrics: Vec<(&str, ObservationsStats, ObservationsStats, f64)>,
     sensitivity: f64,
+    quantiles: &[f64],
     regression_detected: &mut bool,
 ) {
     table.add_row(Row::new(vec![Cell::new(&format!("[{}]", mode))
         .with_style(Attr::Bold)
         .with_hspan(4)]));
 
+    if let Some((this_errors, that_errors)) = errors {
+        let (this_rate, that_rate) = (this_errors.error_rate(), that_errors.error_rate());
+        table.add_row(Row::new(vec![
+            Cell::new("  Error Rate"),
+            Cell::new(&format!("{this_rate:.2}%")),
+            Cell::new(&format!("{that_rate:.2}%")),
+            rate_diff_cell(this_rate, that_rate, sensitivity, regression_detected),
+        ]));
+        table.add_empty_row();
+    }
+
     for (name, this_stats, that_stats, modifier) in metrics {
-        let comparisons = vec![
-            ("Median", this_stats.median, that_stats.median),
-            ("Q95", this_stats.quantile95, that_stats.quantile95),
-            ("Average", this_stats.avg, that_stats.avg),
+        let mut comparisons = vec![
+            ("Median".to_string(), this_stats.median, that_stats.median),
+            ("Q90".into(), this_stats.quantile90, that_stats.quantile90),
+            ("Q95".into(), this_stats.quantile95, that_stats.quantile95),
+            ("Q99".into(), this_stats.quantile99, that_stats.quantile99),
+            (
+                "Q99.9".into(),
+                this_stats.quantile999,
+                that_stats.quantile999,
+            ),
+            ("Average".into(), this_stats.avg, that_stats.avg),
         ];
+        for q in quantiles {
+            let this_value = this_stats.quantile(q / 100.0);
+            let that_value = that_stats.quantile(q / 100.0);
+            if let (Some(this_value), Some(that_value)) = (this_value, that_value) {
+                comparisons.push((format!("Q{q}"), this_value, that_value));
+            }
+        }
 
         for (stat_name, this_value, that_value) in comparisons {
-            let diff = ((this_value as f64 / that_value as f64) * 100.0 - 100.0) * modifier;
-            let mut cell = Cell::new_align(&format!("{diff:>+03.1}%"), Alignment::RIGHT);
-            if diff.abs() > sensitivity && diff.is_sign_positive() {
-                cell.style(Attr::ForegroundColor(RED));
-                *regression_detected = true;
-            } else if diff.abs() > sensitivity && diff.is_sign_negative() {
-                cell.style(Attr::ForegroundColor(GREEN));
-            } else {
-                cell.style(Attr::ForegroundColor(YELLOW));
-            };
-
+            let cell = diff_cell(
+                this_value as f64,
+                that_value as f64,
+                modifier,
+                sensitivity,
+                regression_detected,
+            );
             table.add_row(Row::new(vec![
                 Cell::new(&format!("  {} {}", name, stat_name)),
                 Cell::new(&this_value.to_string()),
@@ -121,3 +182,56 @@ fn add_metrics_to_table(
         table.add_empty_row();
     }
 }
+
+/// # Difference Cell
+///
+/// A helper function to create a colored cell with the relative difference between two
+/// values, flagging a regression if the (modified) difference exceeds the sensitivity.
+fn diff_cell(
+    this_value: f64,
+    that_value: f64,
+    modifier: f64,
+    sensitivity: f64,
+    regression_detected: &mut bool,
+) -> Cell {
+    let diff = ((this_value / that_value) * 100.0 - 100.0) * modifier;
+    let mut cell = Cell::new_align(&format!("{diff:>+03.1}%"), Alignment::RIGHT);
+    if diff.abs() > sensitivity && diff.is_sign_positive() {
+        cell.style(Attr::ForegroundColor(RED));
+        *regression_detected = true;
+    } else if diff.abs() > sensitivity && diff.is_sign_negative() {
+        cell.style(Attr::ForegroundColor(GREEN));
+    } else {
+        cell.style(Attr::ForegroundColor(YELLOW));
+    };
+    cell
+}
+
+/// # Rate Difference Cell
+///
+/// A helper function to create a colored cell with the difference between two rates in
+/// percentage points, flagging a regression if the rate grew by more than the sensitivity.
+/// Unlike the relative difference, it's well-defined for a rate of zero.
+fn rate_diff_cell(
+    this_rate: f64,
+    that_rate: f64,
+    sensitivity: f64,
+    regression_detected: &mut bool,
+) -> Cell {
+    let diff = this_rate - that_rate;
+    if diff == 0.0 {
+        let mut cell = Cell::new_align("0.0pp", Alignment::RIGHT);
+        cell.style(Attr::ForegroundColor(YELLOW));
+        return cell;
+    }
+    let mut cell = Cell::new_align(&format!("{diff:>+03.1}pp"), Alignment::RIGHT);
+    if diff > sensitivity {
+        cell.style(Attr::ForegroundColor(RED));
+        *regression_detected = true;
+    } else if diff < -sensitivity {
+        cell.style(Attr::ForegroundColor(GREEN));
+    } else {
+        cell.style(Attr::ForegroundColor(YELLOW));
+    };
+    cell
+}
@@ -20,14 +20,15 @@ async fn main() -> BenchResult<()> {
     let cmd = AssistCommand::from_args();
     match cmd {
         AssistCommand::Prepare { config } => prepare::prepare(config).await?,
-        AssistCommand::Report { results } => report::report(results)?,
+        AssistCommand::Report { results, quantiles } => report::report(results, quantiles)?,
         AssistCommand::Cleanup { all } => cleanup::cleanup(all),
         AssistCommand::Compare {
             sensitivity,
             silent,
             this,
             that,
-        } => compare::compare(this, that, sensitivity, silent)?,
+            quantiles,
+        } => compare::compare(this, that, sensitivity, silent, quantiles)?,
         AssistCommand::Close { config } => close::close(config).await?,
     }
     Ok(())
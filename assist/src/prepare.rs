@@ -1,14 +1,22 @@
-use core::{config::Config, types::BenchResult};
+use core::{
+    config::{Config, LookupTables},
+    types::{BenchResult, TransactionVersion},
+};
 use std::{
     cell::RefCell, collections::HashSet, fs, hash::Hash, ops::AddAssign, path::PathBuf, rc::Rc,
 };
 
+use alt::{
+    instruction::{create_lookup_table, extend_lookup_table},
+    state::AddressLookupTable,
+};
 use decoder_types::UiAccountEncoding;
 use dlp::{args::DelegateArgs, instruction_builder::delegate};
 use instruction::{AccountMeta, Instruction as SolanaInstruction};
 use keypair::Keypair;
 use program::{
-    instruction::Instruction, DelegateAccountMetas, DelegateAccounts, DELEGATION_PROGRAM_ID,
+    instruction::Instruction, utils::derive_pda, DelegateAccountMetas, DelegateAccounts,
+    DELEGATION_PROGRAM_ID,
 };
 use pubkey::Pubkey;
 use rpc::nonblocking::rpc_client::RpcClient;
@@ -18,6 +26,8 @@ use solana_system_interface::instruction as sysinstruction;
 use transaction::Transaction;
 
 const BENCH_FUNDING: u64 = 200_000_000; // 0.2 SOL
+/// The number of addresses added to a lookup table by a single transaction.
+const LOOKUP_TABLE_EXTEND_CHUNK: usize = 20;
 
 /// Prepares the benchmark environment.
 pub async fn prepare(path: PathBuf) -> BenchResult<()> {
@@ -29,7 +39,11 @@ pub async fn prepare(path: PathBuf) -> BenchResult<()> {
     if !config.gasless {
         preparator.fund_accounts().await?;
     }
-    preparator.initialize_pdas().await
+    preparator.initialize_pdas().await?;
+    if config.benchmark.transaction_version == TransactionVersion::V0 {
+        preparator.create_lookup_tables().await?;
+    }
+    Ok(())
 }
 
 /// Manages benchmark environment preparation.
@@ -220,6 +234,76 @@ impl Preparator {
         Ok(())
     }
 
+    /// Creates an address lookup table on the base chain for the PDAs of every base
+    /// payer, unless an up-to-date one already exists, and clones it to the ER.
+    async fn create_lookup_tables(&self) -> BenchResult<()> {
+        let mut tables = LookupTables::load(&self.config)?;
+        let ephem = crate::common::create_ephem_client(&self.config);
+        let space = self.config.data.account_size as u32;
+        let bases = self.keypairs.iter().step_by(self.config.payers as usize);
+        for base in bases.map(Signer::pubkey) {
+            // The addresses must be in the same order as the accounts of the benchmark
+            let addresses: Vec<Pubkey> = (1..=self.config.benchmark.accounts_count)
+                .map(|seed| derive_pda(base, space, seed, self.config.authority).0)
+                .collect();
+            let existing = match tables.0.get(&base) {
+                Some(table) => Some((*table, self.lookup_table_addresses(table).await)),
+                None => None,
+            };
+            let table = match existing {
+                Some((table, Some(existing))) if existing == addresses => {
+                    tracing::debug!("lookup table {table} is already up-to-date");
+                    table
+                }
+                _ => {
+                    let table = self.create_lookup_table(&addresses).await?;
+                    tables.0.insert(base, table);
+                    tables.save(&self.config)?;
+                    table
+                }
+            };
+            // Reading the table through the ER clones it from the base chain
+            ephem.get_account(&table).await?;
+            tracing::info!("lookup table {table} for the PDAs of {base} is ready");
+        }
+        Ok(())
+    }
+
+    /// Returns the addresses stored in the lookup table, if it exists on the base chain.
+    async fn lookup_table_addresses(&self, table: &Pubkey) -> Option<Vec<Pubkey>> {
+        let account = self.client.get_account(table).await.ok()?;
+        let table = AddressLookupTable::deserialize(&account.data).ok()?;
+        Some(table.addresses.to_vec())
+    }
+
+    /// Creates a lookup table owned by the vault, and extends it with the addresses.
+    async fn create_lookup_table(&self, addresses: &[Pubkey]) -> BenchResult<Pubkey> {
+        let payer = self.vault.pubkey();
+        let slot = self.client.get_slot().await?;
+        let (create_ix, table) = create_lookup_table(payer, payer, slot);
+        let hash = self.client.get_latest_blockhash().await?;
+        let txn =
+            Transaction::new_signed_with_payer(&[create_ix], Some(&payer), &[&self.vault], hash);
+        self.client.send_and_confirm_transaction(&txn).await?;
+
+        for chunk in addresses.chunks(LOOKUP_TABLE_EXTEND_CHUNK) {
+            let extend_ix = extend_lookup_table(table, payer, Some(payer), chunk.to_vec());
+            let hash = self.client.get_latest_blockhash().await?;
+            let txn = Transaction::new_signed_with_payer(
+                &[extend_ix],
+                Some(&payer),
+                &[&self.vault],
+                hash,
+            );
+            self.client.send_and_confirm_transaction(&txn).await?;
+        }
+        tracing::info!(
+            "created lookup table {table} with {} addresses",
+            addresses.len()
+        );
+        Ok(table)
+    }
+
     fn build_init_ix(&self, payer: Pubkey, pda: &Pda) -> SolanaInstruction {
         let ix = Instruction::InitAccount {
             space: pda.space,
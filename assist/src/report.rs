@@ -13,21 +13,26 @@ use crate::latest_run_output_path;
 ///
 /// The main entry point for the `report` command, responsible for orchestrating the
 /// entire report generation process.
-pub fn report(path: Option<PathBuf>) -> BenchResult<()> {
+pub fn report(path: Option<PathBuf>, quantiles: Vec<f64>) -> BenchResult<()> {
     let path = path.unwrap_or_else(|| latest_run_output_path(1));
     let output = fs::read_to_string(path)?;
     let stats: BenchStatistics = json::from_str(&output)?;
 
-    print_stats_pretty(&stats);
+    print_stats_pretty(&stats, &quantiles);
     Ok(())
 }
 
 /// # Print Statistics Pretty
 ///
 /// A helper function to print the benchmark statistics in a human-readable format.
-fn print_stats_pretty(stats: &BenchStatistics) {
+fn print_stats_pretty(stats: &BenchStatistics, quantiles: &[f64]) {
     print_configuration_table(stats);
-    print_results_table(stats);
+    print_results_table(stats, quantiles);
+    print_errors_table(stats);
+    print_websocket_table(stats);
+    print_endpoints_table(stats);
+    print_tiers_table(stats);
+    print_redelegation_table(stats);
 }
 
 /// # Print Configuration Table
@@ -69,76 +74,277 @@ fn print_configuration_table(stats: &BenchStatistics) {
 
 /// # Print Results Table
 ///
-/// A helper function to print the benchmark results in a pretty table.
-fn print_results_table(stats: &BenchStatistics) {
+/// A helper function to print the benchmark results in a pretty table. Any extra
+/// `quantiles` (in percent) are recomputed from the histograms stored in the results.
+fn print_results_table(stats: &BenchStatistics, quantiles: &[f64]) {
     let mut table = Table::new();
-    table.add_row(Row::new(vec![
+    let mut header = vec![
         Cell::new("Metric").with_style(Attr::Bold),
         Cell::new("Observations").with_style(Attr::Bold),
         Cell::new("Median").with_style(Attr::Bold),
+        Cell::new("90th Perc").with_style(Attr::Bold),
+        Cell::new("95th Perc").with_style(Attr::Bold),
+        Cell::new("99th Perc").with_style(Attr::Bold),
+        Cell::new("99.9th Perc").with_style(Attr::Bold),
         Cell::new("Min").with_style(Attr::Bold),
         Cell::new("Max").with_style(Attr::Bold),
         Cell::new("Avg").with_style(Attr::Bold),
-        Cell::new("95th Perc").with_style(Attr::Bold),
         Cell::new("Stddev").with_style(Attr::Bold),
-    ]));
+    ];
+    for q in quantiles {
+        header.push(Cell::new(&format!("{q}th Perc")).with_style(Attr::Bold));
+    }
+    let width = header.len();
+    table.add_row(Row::new(header));
 
-    for (mode, stats) in &stats.request_stats {
-        add_request_stats_to_table(&mut table, mode, *stats);
+    for (mode, request_stats) in &stats.request_stats {
+        let corrected = stats.corrected_request_stats.get(mode);
+        add_request_stats_to_table(&mut table, mode, request_stats, corrected, quantiles, width);
     }
 
     add_stats_row!(
         &mut table,
         "Sig. Confirmation",
-        Some(stats.signature_confirmation_latency)
+        Some(&stats.signature_confirmation_latency),
+        quantiles
+    );
+    add_stats_row!(
+        &mut table,
+        "Sig. Confirmation (CO)",
+        Some(&stats.corrected_signature_confirmation_latency),
+        quantiles
     );
     add_stats_row!(
         &mut table,
         "Acc. Update",
-        Some(stats.account_update_latency)
+        Some(&stats.account_update_latency),
+        quantiles
     );
-    add_stats_row!(&mut table, "Total RPS", Some(stats.rps));
+    add_stats_row!(
+        &mut table,
+        "Acc. Update (CO)",
+        Some(&stats.corrected_account_update_latency),
+        quantiles
+    );
+    add_stats_row!(&mut table, "Total RPS", Some(&stats.rps), quantiles);
+
+    table.printstd();
+}
 
+/// # Print Errors Table
+///
+/// A helper function to print the error and timeout counters of each benchmark mode,
+/// along with the breakdown of the RPC errors, in a pretty table.
+fn print_errors_table(stats: &BenchStatistics) {
+    if stats.errors.is_empty() {
+        return;
+    }
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Mode").with_style(Attr::Bold),
+        Cell::new("Delivered").with_style(Attr::Bold),
+        Cell::new("RPC Errors").with_style(Attr::Bold),
+        Cell::new("Exec. Errors").with_style(Attr::Bold),
+        Cell::new("Acc. Timeouts").with_style(Attr::Bold),
+        Cell::new("Sig. Timeouts").with_style(Attr::Bold),
+        Cell::new("Sig. Mismatches").with_style(Attr::Bold),
+        Cell::new("Error Rate").with_style(Attr::Bold),
+    ]));
+    for (mode, errors) in &stats.errors {
+        table.add_row(Row::new(vec![
+            Cell::new(&format!("[{}]", mode)).with_style(Attr::Bold),
+            Cell::new(&errors.delivered.to_string()),
+            Cell::new(&errors.rpc_errors.to_string()),
+            Cell::new(&errors.executed_with_error.to_string()),
+            Cell::new(&errors.account_update_timeouts.to_string()),
+            Cell::new(&errors.signature_timeouts.to_string()),
+            Cell::new(&errors.signature_mismatches.to_string()),
+            Cell::new(&format!("{:.2}%", errors.error_rate())),
+        ]));
+        for (description, count) in &errors.rpc_error_breakdown {
+            table.add_row(Row::new(vec![
+                Cell::new(&format!("  {}", description)),
+                Cell::new(""),
+                Cell::new(&count.to_string()),
+            ]));
+        }
+    }
+    table.printstd();
+}
+
+/// # Print WebSocket Table
+///
+/// A helper function to print the health of the WebSocket connections, if any of them dropped.
+fn print_websocket_table(stats: &BenchStatistics) {
+    let websocket = stats.websocket;
+    if websocket.reconnects == 0 && websocket.lost_subscriptions == 0 {
+        return;
+    }
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("WS Reconnects").with_style(Attr::Bold),
+        Cell::new("Lost Subscriptions").with_style(Attr::Bold),
+    ]));
+    table.add_row(Row::new(vec![
+        Cell::new(&websocket.reconnects.to_string()),
+        Cell::new(&websocket.lost_subscriptions.to_string()),
+    ]));
+    table.printstd();
+}
+
+/// # Print Endpoints Table
+///
+/// A helper function to print the breakdown of the requests by the ephemeral endpoint,
+/// if they were spread across more than one.
+fn print_endpoints_table(stats: &BenchStatistics) {
+    if stats.endpoints.len() < 2 {
+        return;
+    }
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Endpoint").with_style(Attr::Bold),
+        Cell::new("Requests").with_style(Attr::Bold),
+        Cell::new("Median (μs)").with_style(Attr::Bold),
+        Cell::new("P95 (μs)").with_style(Attr::Bold),
+        Cell::new("P99 (μs)").with_style(Attr::Bold),
+        Cell::new("Confirmation P95 (μs)").with_style(Attr::Bold),
+        Cell::new("Error Rate").with_style(Attr::Bold),
+    ]));
+    let mut endpoints: Vec<_> = stats.endpoints.iter().collect();
+    endpoints.sort_by(|a, b| a.0.cmp(b.0));
+    for (url, endpoint) in endpoints {
+        let latency = &endpoint.request_latency;
+        let confirmation = &endpoint.signature_confirmation_latency;
+        table.add_row(Row::new(vec![
+            Cell::new(url).with_style(Attr::Bold),
+            Cell::new(&endpoint.errors.sent().to_string()),
+            Cell::new(&latency.median.to_string()),
+            Cell::new(&latency.quantile95.to_string()),
+            Cell::new(&latency.quantile99.to_string()),
+            Cell::new(&confirmation.quantile95.to_string()),
+            Cell::new(&format!("{:.2}%", endpoint.errors.error_rate())),
+        ]));
+    }
+    table.printstd();
+}
+
+/// # Print Fee Tiers Table
+///
+/// A helper function to print the landing rate and the confirmation latency of the
+/// transactions in each priority fee tier, if the payers were partitioned into tiers.
+fn print_tiers_table(stats: &BenchStatistics) {
+    if stats.tiers.is_empty() {
+        return;
+    }
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Priority Fee (μlamports/CU)").with_style(Attr::Bold),
+        Cell::new("Sent").with_style(Attr::Bold),
+        Cell::new("Landed").with_style(Attr::Bold),
+        Cell::new("Landing Rate").with_style(Attr::Bold),
+        Cell::new("Median (μs)").with_style(Attr::Bold),
+        Cell::new("P95 (μs)").with_style(Attr::Bold),
+        Cell::new("P99 (μs)").with_style(Attr::Bold),
+    ]));
+    for tier in &stats.tiers {
+        let latency = &tier.signature_confirmation_latency;
+        table.add_row(Row::new(vec![
+            Cell::new(&tier.priority_fee.to_string()).with_style(Attr::Bold),
+            Cell::new(&tier.sent.to_string()),
+            Cell::new(&tier.landed.to_string()),
+            Cell::new(&format!("{:.2}%", tier.landing_rate())),
+            Cell::new(&latency.median.to_string()),
+            Cell::new(&latency.quantile95.to_string()),
+            Cell::new(&latency.quantile99.to_string()),
+        ]));
+    }
+    table.printstd();
+}
+
+/// # Print Re-delegation Table
+///
+/// A helper function to print the undelegate → re-delegate cycles of the accounts,
+/// if any were undelegated by the commit-and-undelegate mode.
+fn print_redelegation_table(stats: &BenchStatistics) {
+    let redelegation = &stats.redelegation;
+    if redelegation.undelegated == 0 {
+        return;
+    }
+    let latency = &redelegation.cycle_latency;
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Undelegated").with_style(Attr::Bold),
+        Cell::new("Re-delegated").with_style(Attr::Bold),
+        Cell::new("Timeouts").with_style(Attr::Bold),
+        Cell::new("In Progress").with_style(Attr::Bold),
+        Cell::new("Starved").with_style(Attr::Bold),
+        Cell::new("Cycle Median (μs)").with_style(Attr::Bold),
+        Cell::new("Cycle P95 (μs)").with_style(Attr::Bold),
+        Cell::new("Cycle P99 (μs)").with_style(Attr::Bold),
+    ]));
+    table.add_row(Row::new(vec![
+        Cell::new(&redelegation.undelegated.to_string()),
+        Cell::new(&redelegation.redelegated.to_string()),
+        Cell::new(&redelegation.timeouts.to_string()),
+        Cell::new(&redelegation.in_progress.to_string()),
+        Cell::new(&redelegation.starved.to_string()),
+        Cell::new(&latency.median.to_string()),
+        Cell::new(&latency.quantile95.to_string()),
+        Cell::new(&latency.quantile99.to_string()),
+    ]));
     table.printstd();
 }
 
 /// # Add RPC Request Stats to Table
 ///
-/// A helper function to add RPC request statistics to the results table.
-fn add_request_stats_to_table(table: &mut Table, mode: &str, stats: ObservationsStats) {
+/// A helper function to add RPC request statistics to the results table, along
+/// with the latencies corrected for coordinated omission (CO), if recorded.
+fn add_request_stats_to_table(
+    table: &mut Table,
+    mode: &str,
+    stats: &ObservationsStats,
+    corrected: Option<&ObservationsStats>,
+    quantiles: &[f64],
+    width: usize,
+) {
     table.add_row(Row::new(vec![Cell::new(&format!("[{}]", mode))
         .with_style(Attr::Bold)
-        .with_hspan(8)]));
-    add_stats_row!(table, "  Request Latency (μs)", Some(stats));
+        .with_hspan(width)]));
+    add_stats_row!(table, "  Request Latency (μs)", Some(stats), quantiles);
+    add_stats_row!(table, "  Request Latency, CO (μs)", corrected, quantiles);
 }
 
 /// # Add Stats Row
 ///
-/// A helper macro to add a row of statistics to the results table.
+/// A helper macro to add a row of statistics to the results table. Extra quantiles
+/// are shown as `---` when the statistics don't carry a histogram to compute them from.
 macro_rules! add_stats_row {
-    ($table:expr, $label:expr, $stats:expr) => {
+    ($table:expr, $label:expr, $stats:expr, $quantiles:expr) => {
         let row = if let Some(stats) = $stats {
-            vec![
+            let mut row = vec![
                 Cell::new($label),
                 Cell::new(&stats.count.to_string()),
                 Cell::new(&stats.median.to_string()),
+                Cell::new(&stats.quantile90.to_string()),
+                Cell::new(&stats.quantile95.to_string()),
+                Cell::new(&stats.quantile99.to_string()),
+                Cell::new(&stats.quantile999.to_string()),
                 Cell::new(&stats.min.to_string()),
                 Cell::new(&stats.max.to_string()),
                 Cell::new(&stats.avg.to_string()),
-                Cell::new(&stats.quantile95.to_string()),
                 Cell::new(&stats.stddev.to_string()),
-            ]
+            ];
+            for q in $quantiles {
+                let value = stats.quantile(q / 100.0);
+                row.push(Cell::new(
+                    &value.map(|v| v.to_string()).unwrap_or("---".into()),
+                ));
+            }
+            row
         } else {
-            vec![
-                Cell::new($label),
-                Cell::new("---"),
-                Cell::new("---"),
-                Cell::new("---"),
-                Cell::new("---"),
-                Cell::new("---"),
-                Cell::new("---"),
-                Cell::new("---"),
-            ]
+            let mut row = vec![Cell::new($label)];
+            row.extend((0..10 + $quantiles.len()).map(|_| Cell::new("---")));
+            row
         };
         $table.add_row(Row::new(row));
     };
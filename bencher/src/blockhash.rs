@@ -1,3 +1,4 @@
+use core::types::Commitment;
 use std::{cell::RefCell, rc::Rc, time::Duration};
 
 use hash::Hash;
@@ -26,14 +27,26 @@ impl BlockHashProvider {
     /// # New Blockhash Provider
     ///
     /// Creates a new `BlockHashProvider`, fetches the initial blockhash, and spawns a
-    /// background task to keep it refreshed.
-    pub async fn new(mut ephem: Connection, shutdown: ShutDownListener) -> BenchResult<Self> {
-        let hash = Self::request(&mut ephem).await?;
+    /// background task to keep it refreshed, both at the given commitment level.
+    pub async fn new(
+        mut ephem: Connection,
+        commitment: Commitment,
+        shutdown: ShutDownListener,
+    ) -> BenchResult<Self> {
+        let hash = Self::request(&mut ephem, commitment).await?;
         let hash = Rc::new(RefCell::new(hash));
-        tokio::task::spawn_local(Self::refresher(ephem, hash.clone(), shutdown));
+        tokio::task::spawn_local(Self::refresher(ephem, hash.clone(), commitment, shutdown));
         Ok(Self { hash })
     }
 
+    /// Creates a provider of a fixed blockhash, which is never refreshed.
+    #[cfg(test)]
+    pub fn fixed(hash: Hash) -> Self {
+        Self {
+            hash: Rc::new(RefCell::new(hash)),
+        }
+    }
+
     /// # Get Blockhash
     ///
     /// Returns the latest cached blockhash.
@@ -44,8 +57,8 @@ impl BlockHashProvider {
     /// # Request Blockhash
     ///
     /// Sends a request to the RPC endpoint to fetch the latest blockhash.
-    async fn request(ephem: &mut Connection) -> BenchResult<Hash> {
-        let request = Request::new(payload::blockhash());
+    async fn request(ephem: &mut Connection, commitment: Commitment) -> BenchResult<Hash> {
+        let request = Request::new(payload::blockhash(commitment));
         ephem
             .send(request, blockhash_extractor)
             .resolve()
@@ -61,13 +74,14 @@ impl BlockHashProvider {
     async fn refresher(
         mut ephem: Connection,
         hash: Rc<RefCell<Hash>>,
+        commitment: Commitment,
         mut shutdown: ShutDownListener,
     ) {
         let mut interval = tokio::time::interval(BLOCKHASH_REFRESH);
         loop {
             tokio::select! {
                 _ = interval.tick() => {
-                    match Self::request(&mut ephem).await {
+                    match Self::request(&mut ephem, commitment).await {
                         Ok(h) => { hash.replace(h); },
                         Err(err) => tracing::warn!("failed to request hash from ephem: {err}"),
                     };
@@ -1,7 +1,12 @@
 // bencher/src/confirmation.rs
 
 use core::stats::{ObservationsStats, StreamingStats};
-use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Instant};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+    time::Instant,
+};
 
 use tokio::sync::{
     mpsc::{self, Receiver, Sender},
@@ -26,6 +31,14 @@ pub struct Confirmations<V> {
     pending: HashMap<u64, PendingConfirmation<V>>,
     /// Streaming statistics for observed latencies (in microseconds).
     stats: StreamingStats,
+    /// Streaming statistics for observed latencies, measured from the intended send
+    /// time of the request, i.e. corrected for coordinated omission (in microseconds).
+    corrected: StreamingStats,
+    /// Streaming statistics for observed latencies, bucketed by the second
+    /// (since `origin`) in which the corresponding request was initiated.
+    timeline: BTreeMap<u64, StreamingStats>,
+    /// The start of the benchmark, from which the timeline seconds are counted.
+    origin: Instant,
     /// The sender part of the confirmation channel.
     pub tx: ConfirmationSender<V>,
 }
@@ -37,10 +50,25 @@ pub struct Confirmations<V> {
 pub struct PendingConfirmation<V> {
     /// The time when the request was initiated.
     start: Instant,
+    /// The time when the request was scheduled to be initiated, which
+    /// precedes the start time if the benchmark fell behind its schedule.
+    intended: Instant,
     /// An optional `oneshot` sender to notify when the confirmation is received.
     tx: Option<oneshot::Sender<V>>,
 }
 
+/// # Confirmation Statistics
+///
+/// The final statistics of all the confirmations, recorded by a `Confirmations` database.
+pub struct ConfirmationStats {
+    /// Latencies measured from the time the request was initiated.
+    pub latency: ObservationsStats,
+    /// Latencies measured from the time the request was scheduled to be initiated.
+    pub corrected: ObservationsStats,
+    /// Per-second latencies, keyed by the second since the origin.
+    pub timeline: BTreeMap<u64, ObservationsStats>,
+}
+
 /// # Event Confirmer
 ///
 /// A generic struct for confirming events, with support for graceful shutdown.
@@ -106,6 +134,9 @@ impl<V> Confirmations<V> {
         let confirmations = Confirmations {
             pending: HashMap::new(),
             stats: StreamingStats::new(),
+            corrected: StreamingStats::new(),
+            timeline: BTreeMap::new(),
+            origin: Instant::now(),
             tx,
         };
         (Rc::new(confirmations.into()), rx)
@@ -119,14 +150,24 @@ impl<V> Confirmations<V> {
     ///
     /// * `id` - The unique identifier for the request.
     /// * `tx` - An optional `oneshot` sender to be notified upon confirmation.
-    pub fn track(&mut self, id: u64, tx: Option<oneshot::Sender<V>>) {
+    /// * `intended` - The time when the request was scheduled to be initiated.
+    pub fn track(&mut self, id: u64, tx: Option<oneshot::Sender<V>>, intended: Instant) {
+        let start = Instant::now();
         let pending = PendingConfirmation {
-            start: Instant::now(),
+            start,
+            intended: intended.min(start),
             tx,
         };
         self.pending.insert(id, pending);
     }
 
+    /// # Set Origin
+    ///
+    /// Sets the start of the benchmark, from which the timeline seconds are counted.
+    pub fn set_origin(&mut self, origin: Instant) {
+        self.origin = origin;
+    }
+
     /// # Remove pending event
     ///
     /// Useful to cleanup events that failed to be confirmed
@@ -148,6 +189,13 @@ impl<V> Confirmations<V> {
         };
         let took = pending.start.elapsed().as_micros() as u32;
         self.stats.push(took);
+        self.corrected
+            .push(pending.intended.elapsed().as_micros() as u32);
+        let second = pending
+            .start
+            .saturating_duration_since(self.origin)
+            .as_secs();
+        self.timeline.entry(second).or_default().push(took);
         if let Some(tx) = pending.tx {
             let _ = tx.send(v);
         }
@@ -155,8 +203,17 @@ impl<V> Confirmations<V> {
 
     /// # Finalize Statistics
     ///
-    /// Calculates and returns the final `ObservationsStats` for all recorded confirmations.
-    pub fn finalize(self) -> ObservationsStats {
-        self.stats.finalize(false)
+    /// Calculates and returns the final `ConfirmationStats` for all recorded confirmations.
+    pub fn finalize(self) -> ConfirmationStats {
+        let timeline = self
+            .timeline
+            .into_iter()
+            .map(|(second, stats)| (second, stats.finalize(false)))
+            .collect();
+        ConfirmationStats {
+            latency: self.stats.finalize(false),
+            corrected: self.corrected.finalize(false),
+            timeline,
+        }
     }
 }
@@ -1,36 +1,68 @@
 use base64::{prelude::BASE64_STANDARD, Engine};
+use core::types::Commitment;
 use hash::{Hash, HASH_BYTES};
 use json::{JsonValueTrait, LazyValue};
+use pubkey::Pubkey;
+use signature::Signature;
 
 /// # Account Update Extractor
 ///
 /// Extracts the account data from an account update notification and returns the first 8 bytes as a `u64`.
 pub fn account_update_extractor(value: LazyValue) -> Option<u64> {
-    let value = value.get("value")?;
+    let data = account_data(value.get("value")?)?;
+    let mut number = [0; 8];
+    number.copy_from_slice(data.get(..8)?);
+    Some(u64::from_le_bytes(number))
+}
+
+/// # Lookup Table Extractor
+///
+/// Extracts the addresses stored in an address lookup table from a `getAccountInfo`
+/// RPC response. Returns `None` if the account doesn't exist.
+pub fn lookup_table_extractor(value: LazyValue) -> Option<Vec<Pubkey>> {
+    let data = account_data(value.get("value")?)?;
+    data.get(LOOKUP_TABLE_META_SIZE..)?
+        .chunks_exact(32)
+        .map(|address| Pubkey::try_from(address).ok())
+        .collect()
+}
 
-    let mut iter = value.get("data").and_then(LazyValue::into_array_iter)?;
+/// The size of the metadata of an address lookup table, which precedes its addresses.
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+
+/// Decodes the data of an account, in any of the encodings supported by the RPC.
+fn account_data(account: LazyValue) -> Option<Vec<u8>> {
+    let mut iter = account.get("data").and_then(LazyValue::into_array_iter)?;
     let data = iter.next();
     let data = data.as_str()?;
-    let data = match iter.next().as_str()? {
-        "base58" => bs58::decode(data).into_vec().ok()?,
-        "base64" => BASE64_STANDARD.decode(data).ok()?,
+    match iter.next().as_str()? {
+        "base58" => bs58::decode(data).into_vec().ok(),
+        "base64" => BASE64_STANDARD.decode(data).ok(),
         "base64+zstd" => {
             let decoded = BASE64_STANDARD.decode(data).ok()?;
-            zstd::decode_all(decoded.as_slice()).ok()?
+            zstd::decode_all(decoded.as_slice()).ok()
         }
-        _ => return None,
-    };
-    let mut number = [0; 8];
-    number.copy_from_slice(data.get(..8)?);
-    Some(u64::from_le_bytes(number))
+        _ => None,
+    }
+}
+
+/// # Reply
+///
+/// The response of the RPC to a benchmark call, which tells that it was delivered.
+#[derive(Debug, PartialEq)]
+pub enum Reply {
+    /// The RPC request returned a value.
+    Value,
+    /// The transaction was accepted, under the returned signature.
+    Signature(Signature),
 }
 
 /// # Value Extractor
 ///
 /// A generic extractor that checks for the presence of a "value" field in the response.
-pub fn value_extractor(value: LazyValue) -> Option<bool> {
-    let v = value.get("value").map(|_| true);
-    if !v.unwrap_or_default() {
+pub fn value_extractor(value: LazyValue) -> Option<Reply> {
+    let v = value.get("value").map(|_| Reply::Value);
+    if v.is_none() {
         tracing::debug!(%value, "got invalid value for an rpc get request");
     }
     v
@@ -38,17 +70,55 @@ pub fn value_extractor(value: LazyValue) -> Option<bool> {
 
 /// # Signature Status Extractor (HTTP)
 ///
-/// Extracts the signature status from an HTTP-based RPC response.
-#[allow(unused)]
-pub fn signature_status_extractor_http(value: LazyValue) -> Option<bool> {
-    Some(!value.is_null())
+/// Extracts the signature statuses from a `getSignatureStatuses` RPC response, in the
+/// order of the requested signatures. Each status is `None` if the transaction is
+/// not yet known, otherwise it holds the commitment level reached by the transaction,
+/// and whether it executed successfully.
+pub fn signature_status_extractor_http(
+    value: LazyValue,
+) -> Option<Vec<Option<(Commitment, bool)>>> {
+    let statuses = value.get("value").and_then(LazyValue::into_array_iter)?;
+    let statuses = statuses
+        .map(|status| {
+            let status = status.ok()?;
+            let success = status.get("err").map(|e| e.is_null())?;
+            // Nodes which don't report the status are assumed to be at `processed`
+            let commitment = status
+                .get("confirmationStatus")
+                .as_str()
+                .and_then(Commitment::parse)
+                .unwrap_or_default();
+            Some((commitment, success))
+        })
+        .collect();
+    Some(statuses)
+}
+
+/// # Account Owners Extractor
+///
+/// Extracts the owners of the accounts from a `getMultipleAccounts` RPC response, in the
+/// order of the requested accounts. The owner is `None` if the account doesn't exist.
+pub fn account_owners_extractor(value: LazyValue) -> Option<Vec<Option<Pubkey>>> {
+    let accounts = value.get("value").and_then(LazyValue::into_array_iter)?;
+    let owners = accounts
+        .map(|account| account.ok()?.get("owner").as_str()?.parse().ok())
+        .collect();
+    Some(owners)
+}
+
+/// # Signature Extractor
+///
+/// Extracts the signature returned by the RPC in a `sendTransaction` response.
+pub fn signature_extractor(value: LazyValue) -> Option<Signature> {
+    value.as_str()?.parse().ok()
 }
 
 /// # Signature Response Extractor
 ///
-/// Extracts the signature from a `sendTransaction` RPC response.
-pub fn signature_response_extractor(value: LazyValue) -> Option<bool> {
-    Some(value.as_str().is_some())
+/// Extracts the signature from a `sendTransaction` RPC response, to be compared with the
+/// one of the sent transaction.
+pub fn signature_response_extractor(value: LazyValue) -> Option<Reply> {
+    signature_extractor(value).map(Reply::Signature)
 }
 
 /// # Signature Status Extractor (WebSocket)
@@ -73,3 +143,49 @@ pub fn blockhash_extractor(value: LazyValue) -> Option<Hash> {
         .expect("failed to decode blockhash");
     Some(Hash::new_from_array(buffer))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(response: &str) -> LazyValue<'_> {
+        json::get(response, ["result"]).unwrap()
+    }
+
+    #[test]
+    fn signature_statuses_follow_the_requested_order() {
+        let response = r#"{"jsonrpc":"2.0","id":1,"result":{"context":{"slot":10},"value":[
+            {"slot":9,"confirmations":null,"err":null,"confirmationStatus":"finalized"},
+            null,
+            {"slot":8,"confirmations":1,"err":{"InstructionError":[0,"Custom"]},"confirmationStatus":"confirmed"},
+            {"slot":7,"confirmations":0,"err":null}
+        ]}}"#;
+        let statuses = signature_status_extractor_http(result(response)).unwrap();
+        assert!(matches!(
+            statuses[..],
+            [
+                Some((Commitment::Finalized, true)),
+                None,
+                Some((Commitment::Confirmed, false)),
+                // Statuses without the commitment level are at `processed`
+                Some((Commitment::Processed, true)),
+            ]
+        ));
+    }
+
+    #[test]
+    fn signature_statuses_require_a_value() {
+        let response = r#"{"jsonrpc":"2.0","id":1,"result":{"context":{"slot":10}}}"#;
+        assert!(signature_status_extractor_http(result(response)).is_none());
+    }
+
+    #[test]
+    fn signature_responses_return_the_signature() {
+        let signature = Signature::from([7; 64]);
+        let response = format!(r#"{{"id":1,"jsonrpc":"2.0","result":"{signature}"}}"#);
+        let reply = signature_response_extractor(result(&response));
+        assert_eq!(reply, Some(Reply::Signature(signature)));
+        let response = r#"{"id":1,"jsonrpc":"2.0","result":"not a signature"}"#;
+        assert_eq!(signature_response_extractor(result(response)), None);
+    }
+}
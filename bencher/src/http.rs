@@ -2,26 +2,35 @@
 //!
 //! Maintains a pool of persistent HTTP/1 or HTTP/2 connections to the validator.
 //! Connections are moved between ready/busy queues based on their state,
-//! providing O(1) connection acquisition.
+//! providing O(1) connection acquisition. Requests are spread across multiple
+//! ephemeral endpoints with a configurable selection policy. Over TLS, the HTTP version is negotiated
+//! with ALPN, preferring the configured one.
 
 use core::config::ConnectionSettings;
-use core::types::{ConnectionType, Url};
+use core::types::{ConnectionType, EndpointSelection, Url};
+use std::cell::Cell;
 use std::collections::VecDeque;
+use std::fmt;
 use std::future::Future;
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
+use std::rc::Rc;
 
 use http_body_util::BodyExt;
 use hyper::body::{Bytes, Incoming};
 use hyper::client::conn::http1::SendRequest as Http1Sender;
 use hyper::client::conn::http2::SendRequest as Http2Sender;
-use hyper::header::{HeaderValue, CONTENT_TYPE};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
 use hyper::{Method, Request, Response, Uri};
 use hyper_util::rt::{TokioExecutor, TokioIo};
-use json::LazyValue;
-use tokio::net::TcpStream;
+use json::{JsonValueTrait, LazyValue};
+use pubkey::Pubkey;
+use rand::{rngs::StdRng, Rng};
 
-use crate::BenchResult;
+use crate::{
+    tls::{self, ALPN_HTTP1, ALPN_HTTP2},
+    BenchResult,
+};
 
 /// # Inner Connection
 ///
@@ -38,49 +47,84 @@ pub enum InnerConnection {
 pub struct Connection {
     inner: InnerConnection,
     uri: Uri,
+    headers: HeaderMap,
+    inflight: Rc<Cell<usize>>,
 }
 
 /// # Connection Pool
 ///
-/// Manages a pool of `Connection` instances with ready/busy queues for O(1) acquisition.
-/// Ready connections are immediately available; busy connections are being prepared.
+/// Manages the connections to each of the ephemeral endpoints, selecting the endpoint
+/// for every request according to the configured policy.
 pub struct ConnectionPool {
+    endpoints: Vec<Endpoint>,
+    selection: EndpointSelection,
+    next: usize,
+    /// The generator of the random selection, derived from the workload seed.
+    rng: StdRng,
+}
+
+/// # Endpoint
+///
+/// Manages a pool of `Connection` instances to a single endpoint, with ready/busy queues
+/// for O(1) acquisition. Ready connections are immediately available; busy connections
+/// are being prepared.
+struct Endpoint {
     ready: VecDeque<Connection>,
     busy: VecDeque<Connection>,
+    inflight: Rc<Cell<usize>>,
 }
 
 impl ConnectionPool {
     /// # New Connection Pool
     ///
-    /// Creates a new `ConnectionPool` with the specified number of connections.
-    /// All connections start in the ready queue.
-    pub async fn new(config: &ConnectionSettings) -> BenchResult<Self> {
+    /// Creates a new `ConnectionPool` with the specified number of connections to every
+    /// ephemeral endpoint. All connections start in the ready queue. The random selection
+    /// policy draws the endpoints from `rng`.
+    pub async fn new(config: &ConnectionSettings, rng: StdRng) -> BenchResult<Self> {
         let count = config.http_connections_count;
-        let mut ready = VecDeque::with_capacity(count);
-        for _ in 0..count {
-            let con = Connection::new(&config.ephem_url, config.http_connection_type).await?;
-            ready.push_back(con);
+        let urls = config.ephem_urls();
+        let mut endpoints = Vec::with_capacity(urls.len());
+        for url in &urls {
+            let inflight = Rc::<Cell<usize>>::default();
+            let mut ready = VecDeque::with_capacity(count);
+            for _ in 0..count {
+                let mut con = Connection::new(url, config.http_connection_type, config).await?;
+                con.inflight = inflight.clone();
+                ready.push_back(con);
+            }
+            endpoints.push(Endpoint {
+                ready,
+                busy: VecDeque::new(),
+                inflight,
+            });
         }
         Ok(Self {
-            ready,
-            busy: VecDeque::new(),
+            endpoints,
+            selection: config.endpoint_selection,
+            next: 0,
+            rng,
         })
     }
 
     /// # Get Connection
     ///
-    /// Obtains a `ConnectionGuard` from the pool with O(1) access from ready queue.
-    /// If no ready connections available, waits for a busy connection to become ready.
-    pub async fn connection(&mut self) -> BenchResult<ConnectionGuard<'_>> {
-        if let Some(con) = self.ready.pop_front() {
+    /// Selects the endpoint for the next request, and obtains a `ConnectionGuard` to it,
+    /// with O(1) access from the ready queue. If no ready connections are available,
+    /// waits for a busy connection to become ready. The payer of the transaction, if
+    /// any, is used by the sticky selection policy.
+    pub async fn connection(&mut self, payer: Option<&Pubkey>) -> BenchResult<ConnectionGuard<'_>> {
+        let index = self.select(payer);
+        let endpoint = &mut self.endpoints[index];
+        if let Some(con) = endpoint.ready.pop_front() {
             return Ok(ConnectionGuard {
                 con: Some(con),
-                pool: self,
+                endpoint,
+                index,
             });
         }
 
         // No ready connections - wait for a busy one to become ready
-        let mut con = self
+        let mut con = endpoint
             .busy
             .pop_front()
             .expect("connection pool should not be empty");
@@ -88,9 +132,41 @@ impl ConnectionPool {
 
         Ok(ConnectionGuard {
             con: Some(con),
-            pool: self,
+            endpoint,
+            index,
         })
     }
+
+    /// Returns the index of the endpoint to send the next request to.
+    fn select(&mut self, payer: Option<&Pubkey>) -> usize {
+        let count = self.endpoints.len();
+        if count == 1 {
+            return 0;
+        }
+        let round_robin = |next: &mut usize| {
+            let index = *next;
+            *next = (index + 1) % count;
+            index
+        };
+        match (self.selection, payer) {
+            (EndpointSelection::RoundRobin, _) => round_robin(&mut self.next),
+            (EndpointSelection::Random, _) => self.rng.gen_range(0..count),
+            (EndpointSelection::LeastInflight, _) => self
+                .endpoints
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, e)| e.inflight.get())
+                .map(|(i, _)| i)
+                .unwrap_or_default(),
+            (EndpointSelection::StickyByPayer, Some(payer)) => {
+                let bytes = payer.to_bytes();
+                let hash = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+                (hash % count as u64) as usize
+            }
+            // Requests without a payer are spread evenly
+            (EndpointSelection::StickyByPayer, None) => round_robin(&mut self.next),
+        }
+    }
 }
 
 impl Connection {
@@ -102,15 +178,20 @@ impl Connection {
         *request.uri_mut() = self.uri.clone();
         *request.method_mut() = Method::POST;
         let ct = HeaderValue::from_static("application/json");
-        request.headers_mut().insert(CONTENT_TYPE, ct);
+        let headers = request.headers_mut();
+        headers.insert(CONTENT_TYPE, ct);
+        headers.extend(self.headers.clone());
+        let inflight = Inflight::new(self.inflight.clone());
         match &mut self.inner {
             InnerConnection::Http1(sender) => ParsedResponse {
                 pending: Box::pin(sender.send_request(request)),
                 extractor,
+                _inflight: inflight,
             },
             InnerConnection::Http2(sender) => ParsedResponse {
                 pending: Box::pin(sender.send_request(request)),
                 extractor,
+                _inflight: inflight,
             },
         }
     }
@@ -130,10 +211,45 @@ impl Connection {
 impl Connection {
     /// # New Connection
     ///
-    /// Establishes a new HTTP connection to the specified URL.
-    pub async fn new(url: &Url, ty: ConnectionType) -> BenchResult<Self> {
-        let stream = TcpStream::connect(url.address(false)).await?;
-        stream.set_nodelay(true).expect("failed to set TCP nodelay");
+    /// Establishes a new HTTP connection to the specified ephemeral endpoint, over TLS for
+    /// `https://` URLs. The extra headers from the connection settings are set on every request.
+    pub async fn new(
+        url: &Url,
+        ty: ConnectionType,
+        config: &ConnectionSettings,
+    ) -> BenchResult<Self> {
+        Self::connect(url, ty, config, headers(config)?).await
+    }
+
+    /// # New Chain Connection
+    ///
+    /// Establishes a new HTTP/2 connection to the main chain node. The extra headers and
+    /// the authentication are meant for the ephemeral endpoints, so they aren't set.
+    pub async fn chain(config: &ConnectionSettings) -> BenchResult<Self> {
+        let url = &config.chain_url;
+        Self::connect(url, ConnectionType::Http2, config, HeaderMap::new()).await
+    }
+
+    /// Establishes a new HTTP connection to the specified URL, which sets the given
+    /// headers on every request.
+    async fn connect(
+        url: &Url,
+        ty: ConnectionType,
+        config: &ConnectionSettings,
+        headers: HeaderMap,
+    ) -> BenchResult<Self> {
+        let alpn: &[&[u8]] = match ty {
+            ConnectionType::Http1 => &[ALPN_HTTP1],
+            ConnectionType::Http2 => &[ALPN_HTTP2, ALPN_HTTP1],
+        };
+        let connector = tls::connector(url, &config.tls, alpn)?;
+        let stream = tls::connect(url, connector.as_ref()).await?;
+        // The server might not support the preferred version of HTTP
+        let ty = match stream.alpn_protocol() {
+            Some(ALPN_HTTP2) => ConnectionType::Http2,
+            Some(_) => ConnectionType::Http1,
+            None => ty,
+        };
 
         let io = TokioIo::new(stream);
 
@@ -154,17 +270,40 @@ impl Connection {
         Ok(Self {
             inner,
             uri: url.0.clone(),
+            headers,
+            inflight: Rc::default(),
         })
     }
 }
 
+/// # Headers
+///
+/// Builds the extra headers, including the authentication, from the connection settings.
+pub fn headers(config: &ConnectionSettings) -> BenchResult<HeaderMap> {
+    let mut headers = HeaderMap::new();
+    for (name, value) in config.headers()? {
+        let name = HeaderName::try_from(name)?;
+        let value = HeaderValue::try_from(value)?;
+        headers.insert(name, value);
+    }
+    Ok(headers)
+}
+
 /// # Connection Guard
 ///
 /// A guard that provides exclusive access to a `Connection` and ensures that it is
 /// returned to the ready queue when dropped.
 pub struct ConnectionGuard<'a> {
     con: Option<Connection>,
-    pool: &'a mut ConnectionPool,
+    endpoint: &'a mut Endpoint,
+    index: usize,
+}
+
+impl ConnectionGuard<'_> {
+    /// Returns the index of the endpoint, in the order of the configured URLs.
+    pub fn endpoint(&self) -> usize {
+        self.index
+    }
 }
 
 impl Deref for ConnectionGuard<'_> {
@@ -183,7 +322,7 @@ impl DerefMut for ConnectionGuard<'_> {
 impl Drop for ConnectionGuard<'_> {
     fn drop(&mut self) {
         if let Some(con) = self.con.take() {
-            self.pool.busy.push_back(con);
+            self.endpoint.busy.push_back(con);
         }
     }
 }
@@ -195,6 +334,25 @@ impl Drop for ConnectionGuard<'_> {
 pub struct ParsedResponse<F> {
     pending: Pin<Box<dyn Future<Output = hyper::Result<Response<Incoming>>> + Send>>,
     extractor: F,
+    _inflight: Inflight,
+}
+
+/// # Inflight Request
+///
+/// Counts a request towards the requests in flight to its endpoint, until dropped.
+struct Inflight(Rc<Cell<usize>>);
+
+impl Inflight {
+    fn new(counter: Rc<Cell<usize>>) -> Self {
+        counter.set(counter.get() + 1);
+        Self(counter)
+    }
+}
+
+impl Drop for Inflight {
+    fn drop(&mut self) {
+        self.0.set(self.0.get() - 1);
+    }
 }
 
 impl<F, V> ParsedResponse<F>
@@ -204,28 +362,17 @@ where
     /// # Resolve Response
     ///
     /// Asynchronously resolves the HTTP response and applies the extractor function
-    /// to parse the response body.
+    /// to parse the response body. If the RPC responded with a JSON-RPC error object
+    /// instead of a result, the returned error is an `RpcError`.
     pub async fn resolve(self) -> BenchResult<Option<V>> {
-        let mut response = self.pending.await?;
-        let mut data = Data::Empty;
-        while let Some(next) = response.frame().await {
-            let Ok(chunk) = next?.into_data() else {
-                continue;
-            };
-            match &mut data {
-                Data::Empty => data = Data::SingleChunk(chunk),
-                Data::SingleChunk(first) => {
-                    let mut buffer = Vec::with_capacity(first.len() + chunk.len());
-                    buffer.extend_from_slice(first);
-                    buffer.extend_from_slice(&chunk);
-                    data = Data::MultiChunk(buffer);
-                }
-                Data::MultiChunk(buffer) => {
-                    buffer.extend_from_slice(&chunk);
-                }
+        let data = collect(self.pending).await?;
+        let result = json::get(data.as_ref(), ["result"]);
+        if result.is_err() {
+            if let Ok(error) = json::get(data.as_ref(), ["error"]) {
+                return Err(RpcError::from(error).into());
             }
         }
-        let result = json::get(data.as_ref(), ["result"]).inspect_err(|_| {
+        let result = result.inspect_err(|_| {
             tracing::error!("failed to parse response: {}", unsafe {
                 std::str::from_utf8_unchecked(data.as_ref())
             })
@@ -233,6 +380,108 @@ where
         Ok((self.extractor)(result))
     }
 }
+
+impl<F, V> ParsedResponse<F>
+where
+    F: Fn(LazyValue) -> Option<V>,
+{
+    /// # Resolve Batch Response
+    ///
+    /// Asynchronously resolves the HTTP response to a JSON-RPC batch request, applying
+    /// the extractor function to the result of every call. The results are paired with
+    /// the IDs of the calls, as the RPC is free to respond to them in any order.
+    pub async fn resolve_batch(self) -> BenchResult<Vec<(u64, BenchResult<Option<V>>)>> {
+        let data = collect(self.pending).await?;
+        let mut results = Vec::new();
+        for response in json::to_array_iter(data.as_ref()) {
+            let response = response.inspect_err(|_| {
+                tracing::error!("failed to parse batch response: {}", unsafe {
+                    std::str::from_utf8_unchecked(data.as_ref())
+                })
+            })?;
+            let Some(id) = response.get("id").as_u64() else {
+                continue;
+            };
+            let result = match (response.get("result"), response.get("error")) {
+                (Some(result), _) => Ok((self.extractor)(result)),
+                (None, Some(error)) => Err(RpcError::from(error).into()),
+                (None, None) => Err("response has neither a result nor an error".into()),
+            };
+            results.push((id, result));
+        }
+        Ok(results)
+    }
+}
+
+/// Collects the body of the HTTP response, avoiding copies for single-chunk bodies.
+async fn collect(
+    pending: Pin<Box<dyn Future<Output = hyper::Result<Response<Incoming>>> + Send>>,
+) -> BenchResult<Data> {
+    let mut response = pending.await?;
+    let mut data = Data::Empty;
+    while let Some(next) = response.frame().await {
+        let Ok(chunk) = next?.into_data() else {
+            continue;
+        };
+        match &mut data {
+            Data::Empty => data = Data::SingleChunk(chunk),
+            Data::SingleChunk(first) => {
+                let mut buffer = Vec::with_capacity(first.len() + chunk.len());
+                buffer.extend_from_slice(first);
+                buffer.extend_from_slice(&chunk);
+                data = Data::MultiChunk(buffer);
+            }
+            Data::MultiChunk(buffer) => {
+                buffer.extend_from_slice(&chunk);
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// # RPC Error
+///
+/// A JSON-RPC error object, returned by the RPC in place of the result, e.g.
+/// when the transaction's blockhash is not found, or its preflight simulation fails.
+#[derive(Debug)]
+pub struct RpcError {
+    /// The JSON-RPC error code.
+    pub code: i64,
+    /// The human-readable description of the error.
+    pub message: String,
+    /// The logs of the preflight transaction simulation, if it was run.
+    pub logs: Vec<String>,
+}
+
+impl From<LazyValue<'_>> for RpcError {
+    fn from(error: LazyValue) -> Self {
+        let code = error.get("code").as_i64().unwrap_or_default();
+        let message = error.get("message").as_str().unwrap_or_default().into();
+        let logs = error
+            .get("data")
+            .get("logs")
+            .and_then(LazyValue::into_array_iter)
+            .map(|logs| {
+                logs.filter_map(|log| log.as_str().map(String::from))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            code,
+            message,
+            logs,
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
 enum Data {
     Empty,
     SingleChunk(Bytes),
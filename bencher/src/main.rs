@@ -1,10 +1,10 @@
-use core::{config::Config, stats::BenchStatistics, types::BenchResult};
+use core::{config::Config, consts::RUNS_OUTPUT_PATH, stats::BenchStatistics, types::BenchResult};
 use std::{
     fs::{self, File},
-    path::PathBuf,
+    path::{Path, PathBuf},
     rc::Rc,
     sync::{
-        atomic::{AtomicBool, AtomicU64, Ordering},
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
     thread::{self, JoinHandle},
@@ -14,6 +14,7 @@ use std::{
 use json::writer::BufferedWriter;
 use keypair::Keypair;
 use runner::BenchRunner;
+use serde::Serialize;
 use signal_hook::{consts::*, low_level};
 use signer::EncodableKey;
 use tokio::{runtime, sync::broadcast, task::LocalSet};
@@ -26,7 +27,7 @@ static SHUTDOWN: AtomicBool = AtomicBool::new(false);
 /// # Main Entry Point
 ///
 /// The main entry point for the Redline bencher, responsible for initializing the configuration,
-/// creating and managing parallel benchmark runners, and aggregating the results.
+/// and dispatching either a single benchmark run or a saturation search.
 fn main() -> BenchResult<()> {
     // Initialize the logger
     tracing_subscriber::fmt()
@@ -34,7 +35,8 @@ fn main() -> BenchResult<()> {
         .init();
 
     // Load the configuration from command-line arguments
-    let config = Config::from_args()?;
+    let command = Command::from_args()?;
+    let config = command.config();
     let keypairs: Vec<_> = (1..=config.payers * config.parallelism)
         .map(|n| Keypair::read_from_file(config.keypairs.join(format!("{n}.json"))))
         .collect::<BenchResult<_>>()?;
@@ -42,22 +44,114 @@ fn main() -> BenchResult<()> {
     // Set up signal handlers for graceful shutdown
     setup_signal_handlers()?;
 
+    let output = match command {
+        Command::Bench(config) => {
+            let stats = bench(&config, &keypairs);
+            write_results(&PathBuf::from(RUNS_OUTPUT_PATH), "redline", &stats)?
+        }
+        Command::Replay(config) => {
+            let stats = bench(&config, &keypairs);
+            write_results(&PathBuf::from(RUNS_OUTPUT_PATH), "redline-replay", &stats)?
+        }
+        Command::Search(config) => {
+            let report = search::search(&config, &keypairs)?;
+            let outdir = PathBuf::from(RUNS_OUTPUT_PATH).join("search");
+            write_results(&outdir, "redline-search", &report)?
+        }
+    };
+
+    if SHUTDOWN.load(Ordering::Relaxed) {
+        tracing::warn!(
+            "Interrupted - partial results saved to {}",
+            output.display()
+        );
+    } else {
+        tracing::info!("Results written to {}", output.display());
+    }
+
+    Ok(())
+}
+
+/// # Top-Level Command
+///
+/// The commands supported by the `redline` binary, parsed from the command-line arguments.
+enum Command {
+    /// Runs a single benchmark: `redline config.toml`
+    Bench(Config),
+    /// Searches for the maximum sustainable rate: `redline search config.toml`
+    Search(Config),
+    /// Replays the recorded trace: `redline replay config.toml`
+    Replay(Config),
+}
+
+impl Command {
+    /// Parses the command and loads the configuration file it refers to.
+    fn from_args() -> BenchResult<Self> {
+        const USAGE: &str = "usage: redline [search|replay] config.toml";
+        let mut args = std::env::args().skip(1);
+        let first = args.next().ok_or(USAGE)?;
+        let (command, path) = match first.as_str() {
+            "search" | "replay" => (first.clone(), args.next().ok_or(USAGE)?),
+            _ => ("bench".into(), first),
+        };
+        tracing::info!("using config file at {path:?} to run the benchmark");
+        let mut config = Config::from_path(path.into())?;
+        match command.as_str() {
+            "search" => Ok(Self::Search(config)),
+            "replay" => {
+                let trace = config
+                    .trace
+                    .as_mut()
+                    .ok_or("the [trace] section is required to replay a trace")?;
+                trace.replay = true;
+                // The replay runs until the end of the trace
+                config.benchmark.iterations = None;
+                config.benchmark.duration = None;
+                Ok(Self::Replay(config))
+            }
+            _ => Ok(Self::Bench(config)),
+        }
+    }
+
+    /// Returns the configuration of the command.
+    fn config(&self) -> &Config {
+        match self {
+            Self::Bench(config) | Self::Search(config) | Self::Replay(config) => config,
+        }
+    }
+}
+
+/// # Run Benchmark
+///
+/// Creates and manages parallel benchmark runners, one per thread, displays
+/// the progress, and aggregates the results once all the runners finish.
+fn bench(config: &Config, keypairs: &[Keypair]) -> BenchStatistics {
     let mut handles = Vec::new();
     // Create a shared atomic counter for tracking progress.
     let progress = Arc::new(AtomicU64::new(0));
+    // The number of runners still running, the bar finishes once all of them are done.
+    let running = Arc::new(AtomicUsize::new(
+        keypairs.chunks(config.payers as usize).len(),
+    ));
 
     // Create and start the progress bar.
     let progress_bar = ProgressBar::new(
-        config.benchmark.iterations * config.parallelism as u64,
+        config
+            .benchmark
+            .iterations
+            .map(|n| n * config.parallelism as u64),
+        config.benchmark.duration(),
         progress.clone(),
+        running.clone(),
     );
     let bar = thread::spawn(move || progress_bar.start());
 
     // Spawn a new thread for each keypair, up to the specified parallelism
-    for kp in keypairs.chunks(config.payers as usize) {
+    for (index, kp) in keypairs.chunks(config.payers as usize).enumerate() {
         let signers = kp.iter().map(|k| k.insecure_clone()).collect();
         let cfg = config.clone();
         let progress = progress.clone();
+        let running = running.clone();
         let handle = thread::spawn(move || {
             let rt = runtime::Builder::new_current_thread()
                 .enable_all()
@@ -65,10 +159,11 @@ fn main() -> BenchResult<()> {
                 .unwrap();
             let local = LocalSet::new();
             let bencher = local
-                .block_on(&rt, BenchRunner::new(signers, cfg, progress))
+                .block_on(&rt, BenchRunner::new(index, signers, cfg, progress))
                 .expect("failed to create bencher");
             let task = local.run_until(bencher.run());
             let results = rt.block_on(task);
+            running.fetch_sub(1, Ordering::Relaxed);
             rt.block_on(local);
             results.stats()
         });
@@ -83,28 +178,22 @@ fn main() -> BenchResult<()> {
         .collect::<thread::Result<Vec<BenchStatistics>>>()
         .expect("failed to join benchmark thread");
 
-    let stats = BenchStatistics::merge(stats);
+    BenchStatistics::merge(stats)
+}
 
-    // Write the aggregated results to a JSON file
+/// # Write Results
+///
+/// Writes the results to a timestamped JSON file in the given directory,
+/// and returns the path to the file.
+fn write_results<T: Serialize>(outdir: &Path, prefix: &str, results: &T) -> BenchResult<PathBuf> {
     let ts = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)?
         .as_secs();
-    let outdir = PathBuf::from("runs");
-    let _ = fs::create_dir(&outdir);
-    let output = outdir.join(format!("redline-{ts:0>12}.json"));
+    let _ = fs::create_dir_all(outdir);
+    let output = outdir.join(format!("{prefix}-{ts:0>12}.json"));
     let writer = File::create(&output).map(BufferedWriter::new)?;
-    json::to_writer(writer, &stats)?;
-
-    if SHUTDOWN.load(Ordering::Relaxed) {
-        tracing::warn!(
-            "Interrupted - partial results saved to {}",
-            output.display()
-        );
-    } else {
-        tracing::info!("Results written to {}", output.display());
-    }
-
-    Ok(())
+    json::to_writer(writer, results)?;
+    Ok(output)
 }
 
 /// Sets up signal handlers for graceful shutdown on SIGTERM/SIGINT
@@ -147,10 +236,16 @@ mod confirmation;
 mod extractor;
 mod http;
 mod payload;
+mod poller;
 mod progress;
 mod rate;
+mod redelegation;
 mod requests;
 mod runner;
+mod search;
+mod selector;
+mod tls;
+mod trace;
 mod transaction;
 mod transfer;
 mod websocket;
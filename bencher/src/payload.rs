@@ -1,91 +1,177 @@
 use base64::{prelude::BASE64_STANDARD, Engine};
-use core::types::AccountEncoding;
+use core::{
+    config::Config,
+    types::{AccountEncoding, Commitment, TransactionEncoding},
+};
 use pubkey::Pubkey;
 use signature::Signature;
-use transaction::Transaction;
+use transaction::versioned::VersionedTransaction;
 
 /// # Blockhash Payload
 ///
 /// Creates a JSON payload for a `getLatestBlockhash` RPC request.
-pub fn blockhash() -> String {
-    r#"{"jsonrpc":"2.0","id":1,"method":"getLatestBlockhash","params":[{"commitment":"processed"}]}"#.into()
+pub fn blockhash(commitment: Commitment) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","id":1,"method":"getLatestBlockhash","params":[{{"commitment":"{}"}}]}}"#,
+        commitment.as_str()
+    )
 }
 
 /// # Account Subscription Payload
 ///
 /// Creates a JSON payload for an `accountSubscribe` RPC request.
-pub fn account_subscription(pubkey: Pubkey, encoding: AccountEncoding, id: u64) -> String {
+pub fn account_subscription(
+    pubkey: Pubkey,
+    encoding: AccountEncoding,
+    commitment: Commitment,
+    id: u64,
+) -> String {
     format!(
-        r#"{{"jsonrpc":"2.0","id":{id},"method":"accountSubscribe","params":["{pubkey}",{{"encoding":"{}","commitment":"processed"}}]}}"#,
-        encoding.as_str()
+        r#"{{"jsonrpc":"2.0","id":{id},"method":"accountSubscribe","params":["{pubkey}",{{"encoding":"{}","commitment":"{}"}}]}}"#,
+        encoding.as_str(),
+        commitment.as_str()
     )
 }
 
 /// # Signature Status Payload
 ///
 /// Creates a JSON payload for a `getSignatureStatuses` RPC request.
-#[allow(unused)]
-pub fn signature_status(txn: &Transaction) -> String {
+pub fn signature_status(signatures: &[Signature]) -> String {
+    let signatures = signatures
+        .iter()
+        .map(|s| format!(r#""{s}""#))
+        .collect::<Vec<_>>()
+        .join(",");
     format!(
-        r#"{{"jsonrpc":"2.0","id":1,"method":"getSignatureStatuses","params":[["{}"]]}}"#,
-        &txn.signatures[0]
+        r#"{{"jsonrpc":"2.0","id":1,"method":"getSignatureStatuses","params":[[{signatures}]]}}"#
     )
 }
 
 /// # Signature Subscription Payload
 ///
 /// Creates a JSON payload for a `signatureSubscribe` RPC request.
-pub fn signature_subscription(signature: Signature, id: u64) -> String {
+pub fn signature_subscription(signature: Signature, commitment: Commitment, id: u64) -> String {
     format!(
-        r#"{{"jsonrpc":"2.0","id":{id},"method":"signatureSubscribe","params":["{signature}",{{"commitment":"processed"}}]}}"#
+        r#"{{"jsonrpc":"2.0","id":{id},"method":"signatureSubscribe","params":["{signature}",{{"commitment":"{}"}}]}}"#,
+        commitment.as_str()
     )
 }
 
+/// # Send Options
+///
+/// The options of a `sendTransaction` request.
+#[derive(Clone, Copy)]
+pub struct SendOptions {
+    /// Whether the RPC should run the preflight check.
+    pub preflight: bool,
+    /// The commitment level of the preflight check.
+    pub preflight_commitment: Commitment,
+    /// The encoding of the serialized transaction.
+    pub encoding: TransactionEncoding,
+    /// The maximum number of times the RPC retries sending the transaction.
+    pub max_retries: Option<usize>,
+    /// The minimum slot at which the RPC may perform the preflight check.
+    pub min_context_slot: Option<u64>,
+}
+
+impl SendOptions {
+    /// Creates the send options for the benchmark transactions from the configuration.
+    pub fn new(config: &Config) -> Self {
+        let settings = &config.benchmark.send_transaction;
+        Self {
+            preflight: config.benchmark.preflight_check,
+            preflight_commitment: settings
+                .preflight_commitment
+                .unwrap_or(config.confirmations.commitment),
+            encoding: settings.encoding,
+            max_retries: settings.max_retries,
+            min_context_slot: settings.min_context_slot,
+        }
+    }
+}
+
 /// # Transaction Payload
 ///
 /// Creates a JSON payload for a `sendTransaction` RPC request.
-pub fn transaction(transaction: &Transaction, check: bool) -> String {
+pub fn transaction(transaction: &VersionedTransaction, options: &SendOptions, id: u64) -> String {
     let serialized = bincode::serialize(transaction).expect("transaction should serialize");
-    let encoded = BASE64_STANDARD.encode(serialized);
+    let encoded = match options.encoding {
+        TransactionEncoding::Base58 => bs58::encode(serialized).into_string(),
+        TransactionEncoding::Base64 => BASE64_STANDARD.encode(serialized),
+    };
+    let mut config = format!(
+        r#""skipPreflight":{},"encoding":"{}","preflightCommitment":"{}""#,
+        !options.preflight,
+        options.encoding.as_str(),
+        options.preflight_commitment.as_str()
+    );
+    if let Some(retries) = options.max_retries {
+        config.push_str(&format!(r#","maxRetries":{retries}"#));
+    }
+    if let Some(slot) = options.min_context_slot {
+        config.push_str(&format!(r#","minContextSlot":{slot}"#));
+    }
     format!(
-        r#"{{"jsonrpc":"2.0","id":1,"method":"sendTransaction","params":["{}",{{"skipPreflight":{},"encoding":"base64", "preflightCommitment": "processed"}}]}}"#,
-        encoded, !check
+        r#"{{"jsonrpc":"2.0","id":{id},"method":"sendTransaction","params":["{encoded}",{{{config}}}]}}"#
     )
 }
 
+/// # Batch Payload
+///
+/// Packs the JSON payloads of multiple calls into a single JSON-RPC batch request.
+pub fn batch(calls: &[String]) -> String {
+    format!("[{}]", calls.join(","))
+}
+
 /// # Get Account Info Payload
 ///
 /// Creates a JSON payload for a `getAccountInfo` RPC request.
-pub fn get_account_info(pubkey: Pubkey, encoding: AccountEncoding, id: u64) -> String {
+pub fn get_account_info(
+    pubkey: Pubkey,
+    encoding: AccountEncoding,
+    commitment: Commitment,
+    id: u64,
+) -> String {
     format!(
-        r#"{{"jsonrpc":"2.0","id":{id},"method":"getAccountInfo","params":["{pubkey}",{{"encoding":"{}"}}]}}"#,
-        encoding.as_str()
+        r#"{{"jsonrpc":"2.0","id":{id},"method":"getAccountInfo","params":["{pubkey}",{{"encoding":"{}","commitment":"{}"}}]}}"#,
+        encoding.as_str(),
+        commitment.as_str()
     )
 }
 
 /// # Get Multiple Accounts Payload
 ///
 /// Creates a JSON payload for a `getMultipleAccounts` RPC request.
-pub fn get_multiple_accounts(pubkeys: &[Pubkey], encoding: AccountEncoding, id: u64) -> String {
+pub fn get_multiple_accounts(
+    pubkeys: &[Pubkey],
+    encoding: AccountEncoding,
+    commitment: Commitment,
+    id: u64,
+) -> String {
     let pubkeys: Vec<String> = pubkeys.iter().map(|pk| pk.to_string()).collect();
     format!(
-        r#"{{"jsonrpc":"2.0","id":{id},"method":"getMultipleAccounts","params":[{pubkeys:?},{{"encoding":"{}"}}]}}"#,
-        encoding.as_str()
+        r#"{{"jsonrpc":"2.0","id":{id},"method":"getMultipleAccounts","params":[{pubkeys:?},{{"encoding":"{}","commitment":"{}"}}]}}"#,
+        encoding.as_str(),
+        commitment.as_str()
     )
 }
 
 /// # Get Balance Payload
 ///
 /// Creates a JSON payload for a `getBalance` RPC request.
-pub fn get_balance(pubkey: Pubkey, id: u64) -> String {
-    format!(r#"{{"jsonrpc":"2.0","id":{id},"method":"getBalance","params":["{pubkey}"]}}"#)
+pub fn get_balance(pubkey: Pubkey, commitment: Commitment, id: u64) -> String {
+    format!(
+        r#"{{"jsonrpc":"2.0","id":{id},"method":"getBalance","params":["{pubkey}",{{"commitment":"{}"}}]}}"#,
+        commitment.as_str()
+    )
 }
 
 /// # Get Token Account Balance Payload
 ///
 /// Creates a JSON payload for a `getTokenAccountBalance` RPC request.
-pub fn get_token_account_balance(pubkey: Pubkey, id: u64) -> String {
+pub fn get_token_account_balance(pubkey: Pubkey, commitment: Commitment, id: u64) -> String {
     format!(
-        r#"{{"jsonrpc":"2.0","id":{id},"method":"getTokenAccountBalance","params":["{pubkey}"]}}"#
+        r#"{{"jsonrpc":"2.0","id":{id},"method":"getTokenAccountBalance","params":["{pubkey}",{{"commitment":"{}"}}]}}"#,
+        commitment.as_str()
     )
 }
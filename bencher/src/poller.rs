@@ -0,0 +1,155 @@
+//! Signature confirmations via `getSignatureStatuses` polling.
+//!
+//! An alternative to `signatureSubscribe`, for RPC providers which rate-limit
+//! or don't support subscriptions. Outstanding signatures are batched, up to
+//! the limit of a single request, and polled at a fixed interval, until they
+//! are either confirmed, or the confirmation timeout expires.
+
+use core::types::Commitment;
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use hyper::Request;
+use signature::Signature;
+use tokio::sync::mpsc::Sender;
+
+use crate::{
+    extractor::signature_status_extractor_http, http::Connection, payload, ShutDownListener,
+};
+
+/// The maximum number of signatures accepted by a single `getSignatureStatuses` request.
+const MAX_SIGNATURES_PER_REQUEST: usize = 256;
+
+/// # Signature Status Poller
+///
+/// A handle for queueing signatures to be polled by a background task, which
+/// reports the confirmations to the signature confirmations database.
+#[derive(Clone)]
+pub struct SignatureStatusPoller {
+    /// Signatures which are yet to be confirmed.
+    outstanding: Rc<RefCell<Vec<OutstandingSignature>>>,
+    /// The commitment level, which the transactions should reach to be confirmed.
+    commitment: Commitment,
+    /// How long a signature is polled, before its confirmation is timed out.
+    timeout: Duration,
+}
+
+/// A signature which is yet to be confirmed.
+struct OutstandingSignature {
+    /// The ID of the request, which sent the transaction.
+    id: u64,
+    /// The signature of the transaction.
+    signature: Signature,
+    /// The time after which the signature is no longer polled.
+    deadline: Instant,
+}
+
+impl SignatureStatusPoller {
+    /// # New Signature Status Poller
+    ///
+    /// Creates a new `SignatureStatusPoller`, and spawns a background task which polls
+    /// the outstanding signatures at the given interval, and sends the confirmations,
+    /// along with whether the transaction executed successfully, to `tx`. Signatures
+    /// are confirmed once their transactions reach the given commitment level, within
+    /// the given timeout.
+    pub fn new(
+        ephem: Connection,
+        tx: Sender<(u64, bool)>,
+        interval: Duration,
+        commitment: Commitment,
+        timeout: Duration,
+        shutdown: ShutDownListener,
+    ) -> Self {
+        let outstanding = Rc::default();
+        let this = Self {
+            outstanding,
+            commitment,
+            timeout,
+        };
+        tokio::task::spawn_local(this.clone().poller(ephem, tx, interval, shutdown));
+        this
+    }
+
+    /// # Poll Signature
+    ///
+    /// Queues the signature of the transaction sent by the request with the given ID.
+    pub fn poll(&self, id: u64, signature: Signature) {
+        let outstanding = OutstandingSignature {
+            id,
+            signature,
+            deadline: Instant::now() + self.timeout,
+        };
+        self.outstanding.borrow_mut().push(outstanding);
+    }
+
+    /// # Signature Poller
+    ///
+    /// A background task that periodically polls the statuses of outstanding signatures.
+    async fn poller(
+        self,
+        mut ephem: Connection,
+        tx: Sender<(u64, bool)>,
+        interval: Duration,
+        mut shutdown: ShutDownListener,
+    ) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.request(&mut ephem, &tx).await;
+                }
+                _ = shutdown.recv() => {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// # Request Statuses
+    ///
+    /// Requests the statuses of all outstanding signatures in batches, and reports
+    /// the confirmed ones. Unconfirmed signatures are kept until their deadline.
+    async fn request(&self, ephem: &mut Connection, tx: &Sender<(u64, bool)>) {
+        let now = Instant::now();
+        let mut outstanding = std::mem::take(&mut *self.outstanding.borrow_mut());
+        outstanding.retain(|s| s.deadline > now);
+
+        let mut unconfirmed = Vec::new();
+        let mut batches = outstanding.into_iter().peekable();
+        while batches.peek().is_some() {
+            let batch: Vec<_> = batches.by_ref().take(MAX_SIGNATURES_PER_REQUEST).collect();
+            let signatures: Vec<_> = batch.iter().map(|s| s.signature).collect();
+            let request = Request::new(payload::signature_status(&signatures));
+            let statuses = ephem
+                .send(request, signature_status_extractor_http)
+                .resolve()
+                .await;
+            let statuses = match statuses {
+                Ok(Some(statuses)) => statuses,
+                Ok(None) => {
+                    tracing::warn!("got invalid response for getSignatureStatuses");
+                    Vec::new()
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "failed to request signature statuses");
+                    Vec::new()
+                }
+            };
+            // Statuses are returned in the order of the requested signatures
+            let mut statuses = statuses.into_iter();
+            for outstanding in batch {
+                match statuses.next().flatten() {
+                    Some((commitment, success)) if commitment >= self.commitment => {
+                        let _ = tx.send((outstanding.id, success)).await;
+                    }
+                    _ => unconfirmed.push(outstanding),
+                }
+            }
+        }
+        // Signatures might have been queued while the requests were in flight
+        self.outstanding.borrow_mut().extend(unconfirmed);
+    }
+}
@@ -1,10 +1,10 @@
 use std::{
     io::{stdout, Write},
     sync::{
-        atomic::{AtomicU64, Ordering},
+        atomic::{AtomicU64, AtomicUsize, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 /// # Progress Bar
@@ -12,18 +12,32 @@ use std::{
 /// A simple, animated progress bar for tracking the progress of the benchmark.
 /// It is designed to run in a separate thread to avoid blocking the main benchmark thread.
 pub struct ProgressBar {
-    /// The total number of iterations in the benchmark.
-    total: u64,
+    /// The total number of iterations in the benchmark, if limited by iterations.
+    total: Option<u64>,
+    /// The wall-clock duration of the benchmark, if limited by time.
+    duration: Option<Duration>,
     /// An atomic counter for tracking the number of completed requests.
     progress: Arc<AtomicU64>,
+    /// The number of benchmark runners, which are still running.
+    running: Arc<AtomicUsize>,
 }
 
 impl ProgressBar {
     /// # New Progress Bar
     ///
     /// Creates a new `ProgressBar` instance.
-    pub fn new(total: u64, progress: Arc<AtomicU64>) -> Self {
-        Self { total, progress }
+    pub fn new(
+        total: Option<u64>,
+        duration: Option<Duration>,
+        progress: Arc<AtomicU64>,
+        running: Arc<AtomicUsize>,
+    ) -> Self {
+        Self {
+            total,
+            duration,
+            progress,
+            running,
+        }
     }
 
     /// # Start Progress Bar
@@ -33,6 +47,9 @@ impl ProgressBar {
         // Unicode spinner characters
         let spinner = ["|", "/", "-", "\\"];
         let mut spin_idx = 0;
+        // The clock starts with the first request, so that the setup
+        // phase of the runners doesn't count towards the benchmark duration
+        let mut started: Option<Instant> = None;
         println!();
         // Hide the cursor for a cleaner look
         print!("\x1B[?25l");
@@ -40,21 +57,30 @@ impl ProgressBar {
         loop {
             let current = self.progress.load(Ordering::Relaxed);
             let shutdown = crate::SHUTDOWN.load(Ordering::Relaxed);
+            if started.is_none() && current > 0 {
+                started = Some(Instant::now());
+            }
+            let elapsed = started.map(|s| s.elapsed()).unwrap_or_default();
+
+            let iterations_done = self.total.is_some_and(|t| current >= t);
+            let duration_done = self.duration.is_some_and(|d| elapsed >= d);
+            // A replay has neither limit, and finishes once all runners are done
+            let runners_done = self.running.load(Ordering::Relaxed) == 0;
 
             // Once done, clear the line, print a final message, and show the cursor
-            if current >= self.total || shutdown {
+            if iterations_done || duration_done || runners_done || shutdown {
                 print!("\r\x1B[K"); // Clear the current line
                 if shutdown {
                     tracing::info!(
-                        "⚠️  Benchmark Interrupted: {}/{} requests sent.",
-                        current,
-                        self.total
+                        "⚠️  Benchmark Interrupted: {} requests sent in {}.",
+                        self.counter(current),
+                        format_duration(elapsed)
                     );
                 } else {
                     tracing::info!(
-                        "✅ Benchmark Complete: {}/{} requests sent.",
-                        current,
-                        self.total
+                        "✅ Benchmark Complete: {} requests sent in {}.",
+                        self.counter(current),
+                        format_duration(elapsed)
                     );
                 }
                 print!("\x1B[?25h"); // Show the cursor again
@@ -62,19 +88,43 @@ impl ProgressBar {
                 break;
             }
 
-            let percent = (current as f64 / self.total as f64) * 100.0;
+            // When both limits are set, the one closer to completion drives the bar
+            let by_iterations = self.total.map(|t| current as f64 / t as f64);
+            let by_duration = self
+                .duration
+                .map(|d| elapsed.as_secs_f64() / d.as_secs_f64());
+            let fraction = by_iterations
+                .into_iter()
+                .chain(by_duration)
+                .fold(0.0, f64::max)
+                .min(1.0);
+            let percent = fraction * 100.0;
             let bar_len = 80;
-            let filled_len = (percent / 100.0 * bar_len as f64) as usize;
+            let filled_len = (fraction * bar_len as f64) as usize;
 
             // Create a bar with Unicode block characters
             let bar = "█".repeat(filled_len);
             let empty = "-".repeat(bar_len - filled_len);
             let spinner_char = spinner[spin_idx % spinner.len()];
 
+            let timing = match self.duration {
+                Some(d) => format!(
+                    " [elapsed {} | remaining {}]",
+                    format_duration(elapsed),
+                    format_duration(d.saturating_sub(elapsed))
+                ),
+                None => String::new(),
+            };
+
             // Use carriage return `\r` to overwrite the line on each update
             print!(
-                "\r {} Running benchmark [{}{}] {:.2}% ({}/{})",
-                spinner_char, bar, empty, percent, current, self.total
+                "\r {} Running benchmark [{}{}] {:.2}% ({}){}",
+                spinner_char,
+                bar,
+                empty,
+                percent,
+                self.counter(current),
+                timing
             );
 
             // We need to flush stdout to ensure the progress bar updates immediately
@@ -85,4 +135,23 @@ impl ProgressBar {
             std::thread::sleep(Duration::from_millis(100));
         }
     }
+
+    /// Formats the number of sent requests, along with the total, if known.
+    fn counter(&self, current: u64) -> String {
+        match self.total {
+            Some(total) => format!("{current}/{total}"),
+            None => current.to_string(),
+        }
+    }
+}
+
+/// Formats a duration as `mm:ss`, or `hh:mm:ss` for durations of an hour or more.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours:02}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes:02}:{seconds:02}")
+    }
 }
@@ -1,10 +1,20 @@
-//! Token-bucket rate limiting with dynamic sleep adjustment.
+//! Schedule-driven rate limiting.
 //!
-//! Maintains target RPS/TPS by calculating adaptive sleep durations.
-//! Sleep time adjusts based on current progress to smooth distribution
-//! across each second.
+//! Maintains target RPS/TPS by following a schedule of intended send
+//! times, spaced evenly at the target rate. If sending falls behind the
+//! schedule (e.g. the validator is slow to respond), the next requests
+//! are sent right away to catch up, and their intended send times are
+//! kept, so that latencies can be corrected for coordinated omission.
+//! The backlog is never skipped: after a stall, all the missed requests
+//! are sent in a burst, bounded only by the concurrency limit, which
+//! keeps the schedule, and thereby the corrected latencies, complete.
+//! The target rate is either fixed, or follows a rate profile,
+//! re-evaluated at the start of each second.
 
-use core::stats::{ObservationsStats, StreamingStats};
+use core::{
+    stats::{ObservationsStats, StreamingStats, TimelineBucket},
+    types::RateProfile,
+};
 use std::{
     sync::Arc,
     time::{Duration, Instant},
@@ -13,7 +23,6 @@ use std::{
 use tokio::sync::{OwnedSemaphorePermit, Semaphore};
 
 const ONESEC: Duration = Duration::from_secs(1);
-const ONEMS: Duration = Duration::from_millis(1);
 
 /// # Rate Manager
 ///
@@ -24,12 +33,20 @@ pub struct RateManager {
     count: u32,
     /// The target rate of requests or transactions per second.
     rate: u32,
+    /// An optional schedule, which the target rate follows over time.
+    profile: Option<RateProfile>,
+    /// The start time of the benchmark.
+    start: Instant,
     /// The start time of the current epoch.
     epoch: Instant,
+    /// The intended send time of the next request, according to the schedule.
+    next: Instant,
     /// A semaphore to control concurrency and prevent overwhelming the validator.
     permits: Arc<Semaphore>,
     /// Streaming statistics for observed rates per second.
     stats: StreamingStats,
+    /// Target and achieved rates for each elapsed epoch.
+    timeline: Vec<TimelineBucket>,
 }
 
 impl RateManager {
@@ -41,45 +58,79 @@ impl RateManager {
     ///
     /// * `concurrency` - The maximum number of concurrent requests.
     /// * `rate` - The target rate of requests or transactions per second.
-    pub fn new(concurrency: usize, rate: u32) -> Self {
+    /// * `profile` - An optional rate profile, which overrides the fixed rate.
+    pub fn new(concurrency: usize, rate: u32, profile: Option<RateProfile>) -> Self {
         let permits = Arc::new(Semaphore::new(concurrency));
-        Self {
+        let now = Instant::now();
+        let mut this = Self {
             rate,
+            profile,
             permits,
             count: 0,
-            epoch: Instant::now(),
+            start: now,
+            epoch: now,
+            next: now,
             stats: StreamingStats::new(),
-        }
+            timeline: Vec::new(),
+        };
+        this.update_rate();
+        this
     }
 
     /// # Tick
     ///
     /// Processes a single request tick, managing the rate and concurrency.
-    /// This method will block if the target rate is exceeded, ensuring a steady load.
-    pub async fn tick(&mut self) -> OwnedSemaphorePermit {
+    /// This method will block until the intended send time of the request,
+    /// which it returns along with the concurrency permit.
+    pub async fn tick(&mut self) -> (OwnedSemaphorePermit, Instant) {
         let elapsed = self.epoch.elapsed();
         self.count += 1;
         if elapsed >= ONESEC {
             self.stats.push(self.count);
+            self.timeline.push(TimelineBucket {
+                second: self.epoch.duration_since(self.start).as_secs(),
+                target_rate: self.rate,
+                achieved_rate: self.count,
+                ..Default::default()
+            });
             self.reset();
+            self.update_rate();
         }
-        let remaining = (self.rate - self.count).max(1) as u64;
-        let mut sleep_dur =
-            Duration::from_millis(1000u64.saturating_sub(elapsed.as_millis() as u64) / remaining);
-        if sleep_dur >= ONEMS {
-            tokio::time::sleep(sleep_dur).await;
-        } else if self.count >= self.rate {
-            sleep_dur = Duration::from_millis(1000u64.saturating_sub(elapsed.as_millis() as u64));
-            tokio::time::sleep(sleep_dur).await;
+        let intended = self.next;
+        self.next += ONESEC / self.rate.max(1);
+        if intended > Instant::now() {
+            tokio::time::sleep_until(intended.into()).await;
         }
-        self.permits.clone().acquire_owned().await.unwrap()
+        let permit = self.permits.clone().acquire_owned().await.unwrap();
+        (permit, intended)
+    }
+
+    /// # Tick At
+    ///
+    /// Same as `tick`, but schedules the request at the given time relative to the start
+    /// of the benchmark, instead of spacing it at the target rate, which is used to replay
+    /// a recorded schedule.
+    pub async fn tick_at(&mut self, at: Duration) -> (OwnedSemaphorePermit, Instant) {
+        self.next = self.start + at;
+        self.tick().await
     }
 
     /// # Get Statistics
     ///
-    /// Returns the final statistics for the observed rates.
-    pub fn stats(self) -> ObservationsStats {
-        self.stats.finalize(true)
+    /// Returns the final statistics for the observed rates, along
+    /// with the per-second timeline of target and achieved rates.
+    pub fn stats(self) -> (ObservationsStats, Vec<TimelineBucket>) {
+        (self.stats.finalize(true), self.timeline)
+    }
+
+    /// # Start
+    ///
+    /// Marks the start of the benchmark, from which the rate profile is evaluated.
+    pub fn start(&mut self, at: Instant) {
+        self.start = at;
+        self.next = at;
+        self.reset();
+        self.update_rate();
     }
 
     #[inline]
@@ -87,4 +138,12 @@ impl RateManager {
         self.epoch = Instant::now();
         self.count = 0;
     }
+
+    /// Re-evaluates the target rate from the rate profile, if one is configured.
+    fn update_rate(&mut self) {
+        if let Some(profile) = &self.profile {
+            // Zero rate is not supported, the lowest possible rate is 1 RPS
+            self.rate = profile.rate_at(self.start.elapsed()).max(1);
+        }
+    }
 }
@@ -0,0 +1,383 @@
+//! Re-delegation of the accounts undelegated by the commit-and-undelegate mode.
+//!
+//! Every account sent to be undelegated is taken out of the pool, which the transactions
+//! draw their accounts from, and watched on the base chain. Once the undelegation lands
+//! there, the vault delegates the account to the ER again, and the account returns to
+//! the pool as soon as the delegation lands too, so that the pool doesn't shrink during
+//! the run. The accounts of the transactions which are held back, fail to be delivered,
+//! or execute with an error, return to the pool right away. The time from sending the
+//! undelegation to the landing of the re-delegation is reported as the latency of the
+//! full cycle.
+
+use core::{
+    config::Config,
+    stats::{RedelegationStats, StreamingStats},
+    types::{AccountEncoding, BenchResult, Commitment},
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use hyper::Request;
+use instruction::{AccountMeta, Instruction as SolanaInstruction};
+use keypair::Keypair;
+use program::{
+    instruction::Instruction, utils::derive_pda, DelegateAccountMetas, DelegateAccounts,
+    DELEGATION_PROGRAM_ID,
+};
+use pubkey::Pubkey;
+use signer::{EncodableKey, Signer};
+use transaction::Transaction;
+
+use crate::{
+    blockhash::BlockHashProvider,
+    extractor::{account_owners_extractor, signature_extractor},
+    http::Connection,
+    payload::{self, SendOptions},
+    ShutDown, ShutDownListener,
+};
+
+/// The interval at which the owners of the accounts in the cycle are polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long to wait for an undelegation to land on the base chain, after which the
+/// account is assumed to have stayed delegated, e.g. because its transaction failed.
+const UNDELEGATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait for a re-delegation to land on the base chain, before resending it.
+const REDELEGATION_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long to wait for the cycles in progress to complete at the end of the benchmark.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+/// The maximum number of accounts accepted by a single `getMultipleAccounts` request.
+const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// # Re-delegation Manager
+///
+/// A handle for checking out the accounts, which are about to be undelegated, shared
+/// with a background task that delegates them again once their undelegation lands.
+#[derive(Clone)]
+pub struct RedelegationManager {
+    state: Rc<RefCell<RedelegationState>>,
+    /// The PDAs of the base keypair, in the order of their seeds.
+    accounts: Rc<[Pubkey]>,
+}
+
+/// The state of the cycles, shared between the handles and the background task.
+#[derive(Default)]
+struct RedelegationState {
+    /// The accounts in the cycle, which the transactions shouldn't use until it completes.
+    cycling: HashMap<Pubkey, Cycle>,
+    /// Whether the benchmark has started, before which the accounts are not tracked.
+    started: bool,
+    undelegated: u64,
+    redelegated: u64,
+    timeouts: u64,
+    starved: u64,
+    latency: StreamingStats,
+}
+
+/// An account in the cycle.
+struct Cycle {
+    /// The time at which the undelegation was sent.
+    start: Instant,
+    /// Whether the undelegation has landed, and the re-delegation was sent.
+    redelegating: bool,
+    /// The time after which the current phase of the cycle has timed out.
+    deadline: Instant,
+}
+
+/// The background task, which re-delegates the undelegated accounts on the base chain.
+struct Redelegator {
+    state: Rc<RefCell<RedelegationState>>,
+    /// The HTTP connection to the base chain's RPC endpoint.
+    chain: Connection,
+    /// A provider of the base chain's blockhash for the re-delegations.
+    blockhash: BlockHashProvider,
+    /// The vault, which owns the PDAs and pays for their re-delegations.
+    vault: Keypair,
+    /// The base keypair, from which the PDAs are derived.
+    base: Pubkey,
+    /// The seeds of the PDAs, by their addresses.
+    seeds: HashMap<Pubkey, u8>,
+    /// The validator, which the PDAs are delegated to.
+    authority: Pubkey,
+    /// The commitment level, at which the owners of the accounts are polled.
+    commitment: Commitment,
+    /// The options of the re-delegation transactions, which skip the preflight check.
+    options: SendOptions,
+}
+
+impl RedelegationManager {
+    /// # New Re-delegation Manager
+    ///
+    /// Creates a new `RedelegationManager` for the PDAs derived from the given base
+    /// keypair, and spawns a background task, which polls the accounts in the cycle
+    /// on the base chain and re-delegates them on behalf of the vault.
+    pub async fn new(config: &Config, base: Pubkey, shutdown: &ShutDown) -> BenchResult<Self> {
+        let vault = Keypair::read_from_file(config.keypairs.join("vault.json"))?;
+        let connect = || Connection::chain(&config.connection);
+        let commitment = config.confirmations.commitment;
+        let blockhash =
+            BlockHashProvider::new(connect().await?, commitment, shutdown.listener()).await?;
+        let space = config.data.account_size as u32;
+        let accounts: Rc<[Pubkey]> = (1..=config.benchmark.accounts_count)
+            .map(|seed| derive_pda(base, space, seed, config.authority).0)
+            .collect();
+        let seeds = accounts.iter().copied().zip(1..).collect();
+        let state = Rc::<RefCell<RedelegationState>>::default();
+        let redelegator = Redelegator {
+            state: state.clone(),
+            chain: connect().await?,
+            blockhash,
+            vault,
+            base,
+            seeds,
+            authority: config.authority,
+            commitment,
+            options: SendOptions {
+                preflight: false,
+                ..SendOptions::new(config)
+            },
+        };
+        tokio::task::spawn_local(redelegator.run(shutdown.listener()));
+        Ok(Self { state, accounts })
+    }
+
+    /// Creates a manager of the given accounts without the background task, so that
+    /// their cycles never complete.
+    #[cfg(test)]
+    pub fn detached(accounts: Vec<Pubkey>) -> Self {
+        Self {
+            state: Rc::default(),
+            accounts: accounts.into(),
+        }
+    }
+
+    /// Returns the PDAs of the base keypair, in the order of their seeds, which is also
+    /// the order of the addresses in its lookup table.
+    pub fn accounts(&self) -> &[Pubkey] {
+        &self.accounts
+    }
+
+    /// # Start Tracking
+    ///
+    /// Marks the start of the benchmark, after which the checked out accounts are
+    /// tracked. Transactions generated before, e.g. to check their size, are never sent.
+    pub fn start(&self) {
+        self.state.borrow_mut().started = true;
+    }
+
+    /// Tells whether the account is in the cycle, so it shouldn't be used by transactions.
+    pub fn cycling(&self, account: &Pubkey) -> bool {
+        self.state.borrow().cycling.contains_key(account)
+    }
+
+    /// # Hold Back Request
+    ///
+    /// Counts a request which wasn't sent, since all the accounts of one of its calls were
+    /// in the cycle, and returns the accounts checked out by its calls to the pool.
+    pub fn hold_back(&self, undelegated: &[Vec<Pubkey>]) {
+        for accounts in undelegated {
+            self.release(accounts);
+        }
+        self.state.borrow_mut().starved += 1;
+    }
+
+    /// # Release Accounts
+    ///
+    /// Returns the accounts checked out by a transaction, which was never sent or didn't
+    /// execute successfully, to the pool, unless their undelegation has already landed.
+    pub fn release(&self, accounts: &[Pubkey]) {
+        let mut state = self.state.borrow_mut();
+        for pk in accounts {
+            if state
+                .cycling
+                .get(pk)
+                .is_some_and(|cycle| !cycle.redelegating)
+            {
+                state.cycling.remove(pk);
+                state.undelegated -= 1;
+            }
+        }
+    }
+
+    /// # Check Out Accounts
+    ///
+    /// Filters out the selected accounts which are still in the cycle, and starts the
+    /// cycle of the remaining ones, which the transaction is about to undelegate.
+    pub fn checkout(&self, accounts: Vec<Pubkey>) -> Vec<Pubkey> {
+        let mut state = self.state.borrow_mut();
+        if !state.started {
+            return accounts;
+        }
+        let accounts: Vec<_> = accounts
+            .into_iter()
+            .filter(|pk| !state.cycling.contains_key(pk))
+            .collect();
+        let now = Instant::now();
+        // A replayed trace might have been recorded with the accounts of another payer,
+        // which can't be re-delegated without their base keypair
+        for &pk in accounts.iter().filter(|pk| self.accounts.contains(pk)) {
+            let cycle = Cycle {
+                start: now,
+                redelegating: false,
+                deadline: now + UNDELEGATION_TIMEOUT,
+            };
+            state.cycling.insert(pk, cycle);
+            state.undelegated += 1;
+        }
+        accounts
+    }
+
+    /// # Drain Cycles
+    ///
+    /// Waits for the cycles in progress to complete, for at most `DRAIN_TIMEOUT`, so
+    /// that the accounts aren't left undelegated once the benchmark is over.
+    pub async fn drain(&self) {
+        let pending = self.state.borrow().cycling.len();
+        if pending == 0 {
+            return;
+        }
+        tracing::info!("waiting for {pending} account(s) to be re-delegated");
+        let deadline = Instant::now() + DRAIN_TIMEOUT;
+        while Instant::now() < deadline && !self.state.borrow().cycling.is_empty() {
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+        let pending = self.state.borrow().cycling.len();
+        if pending > 0 {
+            tracing::warn!(
+                "{pending} account(s) might be left undelegated, run `assist prepare` to delegate them"
+            );
+        }
+    }
+
+    /// # Re-delegation Statistics
+    ///
+    /// Returns the statistics of the cycles, counting the ones which are still in progress.
+    pub fn stats(&self) -> RedelegationStats {
+        let mut state = self.state.borrow_mut();
+        RedelegationStats {
+            undelegated: state.undelegated,
+            redelegated: state.redelegated,
+            timeouts: state.timeouts,
+            in_progress: state.cycling.len() as u64,
+            starved: state.starved,
+            cycle_latency: std::mem::take(&mut state.latency).finalize(false),
+        }
+    }
+}
+
+impl Redelegator {
+    /// # Re-delegator
+    ///
+    /// A background task that periodically polls the accounts in the cycle.
+    async fn run(mut self, mut shutdown: ShutDownListener) {
+        let mut interval = tokio::time::interval(POLL_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    self.poll().await;
+                }
+                _ = shutdown.recv() => {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// # Poll Owners
+    ///
+    /// Requests the owners of the accounts in the cycle on the base chain, and advances
+    /// their cycles: an account owned by the delegation program is delegated, while any
+    /// other owner means that its undelegation has landed.
+    async fn poll(&mut self) {
+        let accounts: Vec<Pubkey> = self.state.borrow().cycling.keys().copied().collect();
+        for chunk in accounts.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+            // Only the owners are of interest, so the encoding of the data doesn't matter
+            let encoding = AccountEncoding::Base64;
+            let payload = payload::get_multiple_accounts(chunk, encoding, self.commitment, 1);
+            let owners = self
+                .chain
+                .send(Request::new(payload), account_owners_extractor)
+                .resolve()
+                .await;
+            let owners = match owners {
+                Ok(Some(owners)) => owners,
+                Ok(None) => {
+                    tracing::warn!("got invalid response for getMultipleAccounts");
+                    continue;
+                }
+                Err(error) => {
+                    tracing::warn!(%error, "failed to request the owners of undelegated accounts");
+                    continue;
+                }
+            };
+            let now = Instant::now();
+            // Owners are returned in the order of the requested accounts
+            for (&pda, owner) in chunk.iter().zip(owners) {
+                let delegated = owner == Some(DELEGATION_PROGRAM_ID);
+                let mut state = self.state.borrow_mut();
+                let Some(cycle) = state.cycling.get_mut(&pda) else {
+                    continue;
+                };
+                match (cycle.redelegating, delegated) {
+                    // The undelegation has landed, so the account can be delegated again
+                    (false, false) => {
+                        cycle.redelegating = true;
+                        cycle.deadline = now + REDELEGATION_TIMEOUT;
+                        drop(state);
+                        self.redelegate(pda);
+                    }
+                    // The re-delegation has landed, which completes the cycle
+                    (true, true) => {
+                        let took = cycle.start.elapsed().as_micros() as u32;
+                        state.cycling.remove(&pda);
+                        state.redelegated += 1;
+                        state.latency.push(took);
+                    }
+                    // The undelegation never landed, so the account is still delegated
+                    (false, true) if now > cycle.deadline => {
+                        state.cycling.remove(&pda);
+                        state.timeouts += 1;
+                    }
+                    // The re-delegation might have been dropped, so it's sent again
+                    (true, false) if now > cycle.deadline => {
+                        cycle.deadline = now + REDELEGATION_TIMEOUT;
+                        drop(state);
+                        self.redelegate(pda);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// # Re-delegate PDA
+    ///
+    /// Sends the transaction, which delegates the PDA to the ER again, paid for by the vault.
+    fn redelegate(&mut self, pda: Pubkey) {
+        let payer = self.vault.pubkey();
+        let ix = Instruction::Delegate {
+            seed: self.seeds[&pda],
+            authority: self.authority,
+        };
+        let accounts = DelegateAccounts::new(pda, program::ID);
+        let mut metas = DelegateAccountMetas::from(accounts).into_vec(payer);
+        metas.push(AccountMeta::new_readonly(self.base, false));
+        let ix = SolanaInstruction::new_with_bincode(program::ID, &ix, metas);
+        let blockhash = self.blockhash.hash();
+        let txn =
+            Transaction::new_signed_with_payer(&[ix], Some(&payer), &[&self.vault], blockhash);
+        let request = Request::new(payload::transaction(&txn.into(), &self.options, 1));
+
+        let response = self.chain.send(request, signature_extractor);
+        tokio::task::spawn_local(async move {
+            match response.resolve().await {
+                Ok(Some(signature)) => tracing::debug!(%signature, %pda, "re-delegated pda"),
+                Ok(None) => tracing::warn!(%pda, "got no signature for the re-delegation of pda"),
+                Err(error) => tracing::warn!(%error, %pda, "failed to re-delegate pda"),
+            }
+        });
+    }
+}
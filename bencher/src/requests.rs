@@ -1,25 +1,29 @@
 use core::{
-    config::Config,
-    types::{AccountEncoding, BenchMode},
+    config::{Config, LookupTables},
+    types::{AccountEncoding, AccountSelection, BenchMode, Commitment, TransactionVersion},
 };
+use hash::Hash;
 use hyper::Request;
 use keypair::Keypair;
+use message::AddressLookupTableAccount;
 use program::utils::derive_pda;
 use pubkey::Pubkey;
 use signature::Signature;
 use signer::Signer;
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Duration};
+use transaction::versioned::VersionedTransaction;
 
 use crate::{
     blockhash::BlockHashProvider,
-    extractor::{signature_response_extractor, value_extractor},
-    payload,
-    transaction::TransactionProvider,
-};
-use rand::{
-    distributions::WeightedIndex, prelude::Distribution, rngs::ThreadRng, seq::SliceRandom,
-    thread_rng,
+    extractor::{lookup_table_extractor, signature_response_extractor, value_extractor, Reply},
+    http::Connection,
+    payload::{self, SendOptions},
+    redelegation::RedelegationManager,
+    selector::AccountSelector,
+    transaction::{make_provider, ComputeBudget, TransactionProvider, MAX_TRANSACTION_SIZE},
+    BenchResult,
 };
+use rand::{distributions::WeightedIndex, prelude::Distribution, rngs::StdRng, Rng, SeedableRng};
 
 /// # Request Builder Trait
 ///
@@ -38,12 +42,62 @@ pub trait RequestBuilder {
     fn signature(&self) -> Option<Signature> {
         None
     }
+    /// Returns the signatures of the last generated transactions, one for every call
+    /// in the last request, if applicable.
+    fn signatures(&self) -> Vec<Signature> {
+        self.signature().into_iter().collect()
+    }
+    /// Returns the number of JSON-RPC calls in the last generated request. The calls are
+    /// identified by consecutive IDs, starting from the one passed to `build`.
+    fn batch_size(&self) -> usize {
+        1
+    }
+    /// Returns the fee payer of the last generated transaction, if applicable.
+    fn payer(&self) -> Option<Pubkey> {
+        None
+    }
+    /// Returns the priority fee tier of the last generated transaction, if its
+    /// payers are partitioned into fee tiers.
+    fn fee_tier(&self) -> Option<u64> {
+        None
+    }
+    /// Returns the priority fee tiers of the last generated transactions, one for
+    /// every signature in the last request.
+    fn fee_tiers(&self) -> Vec<Option<u64>> {
+        self.signature()
+            .map(|_| self.fee_tier())
+            .into_iter()
+            .collect()
+    }
+    /// Returns the transactions of the last generated request, if applicable.
+    fn transactions(&self) -> Vec<VersionedTransaction> {
+        Vec::new()
+    }
+    /// Returns the time, relative to the start of the benchmark, at which the next
+    /// request is due, if the builder follows a recorded schedule.
+    fn due(&self) -> Option<Duration> {
+        None
+    }
+    /// Returns whether the builder has run out of requests to build.
+    fn exhausted(&self) -> bool {
+        false
+    }
+    /// Returns whether the last generated request has no accounts to operate on, since
+    /// all of them were taken out of the pool, so it shouldn't be sent.
+    fn starved(&self) -> bool {
+        false
+    }
+    /// Returns the accounts checked out for undelegation by the last generated request,
+    /// one list for every call in it, if applicable.
+    fn undelegated(&self) -> Vec<Vec<Pubkey>> {
+        Vec::new()
+    }
     /// Returns a list of accounts used by the request builder.
     fn accounts(&self) -> Vec<Pubkey> {
         vec![]
     }
     /// Returns the extractor function for the request builder.
-    fn extractor(&self) -> fn(json::LazyValue) -> Option<bool>;
+    fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply>;
 }
 
 // --- Transaction Request Builders ---
@@ -56,8 +110,34 @@ pub struct TransactionRequestBuilder {
     signers: Vec<Keypair>,
     blockhash_provider: BlockHashProvider,
     signature: Option<Signature>,
-    preflight: bool,
-    rng: ThreadRng,
+    transaction: Option<VersionedTransaction>,
+    payer: Option<Pubkey>,
+    options: SendOptions,
+    budget: ComputeBudget,
+    tables: Vec<AddressLookupTableAccount>,
+    rng: StdRng,
+}
+
+impl TransactionRequestBuilder {
+    /// Checks that the transactions of the mode fit into a single packet, by generating
+    /// a sample one, so that oversized transactions are reported before the benchmark.
+    fn check_size(&mut self) -> BenchResult<()> {
+        let budget = self.budget.instructions(0);
+        let signer = &self.signers[0];
+        let tx = self
+            .provider
+            .generate(0, Hash::default(), signer, &budget, &self.tables);
+        let size = bincode::serialized_size(&tx)?;
+        if size > MAX_TRANSACTION_SIZE {
+            let name = self.provider.name();
+            let error = format!(
+                "{name} transactions take up {size} bytes, over the limit of {MAX_TRANSACTION_SIZE} bytes, \
+                 use fewer accounts or instructions, or v0 transactions"
+            );
+            return Err(error.into());
+        }
+        Ok(())
+    }
 }
 
 impl RequestBuilder for TransactionRequestBuilder {
@@ -66,21 +146,41 @@ impl RequestBuilder for TransactionRequestBuilder {
     }
     fn build(&mut self, id: u64) -> Request<String> {
         let blockhash = self.blockhash_provider.hash();
-        let signer = self
-            .signers
-            .choose(&mut self.rng)
-            .expect("should have at least one signer");
-        let tx = self.provider.generate(id, blockhash, signer);
+        let index = self.rng.gen_range(0..self.signers.len());
+        let signer = &self.signers[index];
+        let budget = self.budget.instructions(index);
+        self.provider.set_payer(index);
+        let tx = self
+            .provider
+            .generate(id, blockhash, signer, &budget, &self.tables);
         self.signature.replace(tx.signatures[0]);
-        Request::new(payload::transaction(&tx, self.preflight))
+        self.payer.replace(signer.pubkey());
+        let request = Request::new(payload::transaction(&tx, &self.options, id));
+        self.transaction.replace(tx);
+        request
     }
     fn signature(&self) -> Option<Signature> {
         self.signature
     }
+    fn transactions(&self) -> Vec<VersionedTransaction> {
+        self.transaction.iter().cloned().collect()
+    }
+    fn payer(&self) -> Option<Pubkey> {
+        self.payer
+    }
+    fn fee_tier(&self) -> Option<u64> {
+        self.budget.tier()
+    }
+    fn starved(&self) -> bool {
+        self.provider.starved()
+    }
+    fn undelegated(&self) -> Vec<Vec<Pubkey>> {
+        vec![self.provider.undelegated()]
+    }
     fn accounts(&self) -> Vec<Pubkey> {
         self.provider.accounts()
     }
-    fn extractor(&self) -> fn(json::LazyValue) -> Option<bool> {
+    fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply> {
         signature_response_extractor
     }
 }
@@ -91,7 +191,7 @@ impl RequestBuilder for TransactionRequestBuilder {
 ///
 /// A generic request builder for RPC calls that select a single account.
 struct RpcRequestBuilder<F> {
-    accounts: Vec<Pubkey>,
+    selector: AccountSelector,
     payload_fn: F,
     name: &'static str,
 }
@@ -100,9 +200,9 @@ impl<F> RpcRequestBuilder<F>
 where
     F: FnMut(Pubkey, u64) -> String,
 {
-    fn new(accounts: Vec<Pubkey>, payload_fn: F, name: &'static str) -> Self {
+    fn new(selector: AccountSelector, payload_fn: F, name: &'static str) -> Self {
         Self {
-            accounts,
+            selector,
             payload_fn,
             name,
         }
@@ -118,11 +218,11 @@ where
     }
 
     fn build(&mut self, id: u64) -> Request<String> {
-        let pubkey = self.accounts[id as usize % self.accounts.len()];
+        let pubkey = self.selector.select(1)[0];
         Request::new((self.payload_fn)(pubkey, id))
     }
 
-    fn extractor(&self) -> fn(json::LazyValue) -> Option<bool> {
+    fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply> {
         value_extractor
     }
 }
@@ -133,6 +233,7 @@ where
 pub struct GetMultipleAccountsRequestBuilder {
     accounts: Vec<Pubkey>,
     encoding: AccountEncoding,
+    commitment: Commitment,
 }
 
 impl RequestBuilder for GetMultipleAccountsRequestBuilder {
@@ -143,10 +244,11 @@ impl RequestBuilder for GetMultipleAccountsRequestBuilder {
         Request::new(payload::get_multiple_accounts(
             &self.accounts,
             self.encoding,
+            self.commitment,
             id,
         ))
     }
-    fn extractor(&self) -> fn(json::LazyValue) -> Option<bool> {
+    fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply> {
         value_extractor
     }
 }
@@ -157,7 +259,7 @@ impl RequestBuilder for GetMultipleAccountsRequestBuilder {
 pub struct MixedRequestBuilder {
     providers: Vec<Box<dyn RequestBuilder>>,
     distribution: WeightedIndex<u16>,
-    rng: ThreadRng,
+    rng: StdRng,
     last_name: &'static str,
     last_index: usize,
 }
@@ -176,6 +278,30 @@ impl RequestBuilder for MixedRequestBuilder {
     fn signature(&self) -> Option<Signature> {
         self.providers[self.last_index].signature()
     }
+    fn signatures(&self) -> Vec<Signature> {
+        self.providers[self.last_index].signatures()
+    }
+    fn batch_size(&self) -> usize {
+        self.providers[self.last_index].batch_size()
+    }
+    fn payer(&self) -> Option<Pubkey> {
+        self.providers[self.last_index].payer()
+    }
+    fn fee_tier(&self) -> Option<u64> {
+        self.providers[self.last_index].fee_tier()
+    }
+    fn fee_tiers(&self) -> Vec<Option<u64>> {
+        self.providers[self.last_index].fee_tiers()
+    }
+    fn transactions(&self) -> Vec<VersionedTransaction> {
+        self.providers[self.last_index].transactions()
+    }
+    fn starved(&self) -> bool {
+        self.providers[self.last_index].starved()
+    }
+    fn undelegated(&self) -> Vec<Vec<Pubkey>> {
+        self.providers[self.last_index].undelegated()
+    }
     fn accounts(&self) -> Vec<Pubkey> {
         self.providers
             .iter()
@@ -184,7 +310,7 @@ impl RequestBuilder for MixedRequestBuilder {
             .into_iter()
             .collect()
     }
-    fn extractor(&self) -> fn(json::LazyValue) -> Option<bool> {
+    fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply> {
         self.providers
             .iter()
             .find(|p| p.name() == self.last_name)
@@ -193,38 +319,213 @@ impl RequestBuilder for MixedRequestBuilder {
     }
 }
 
+/// # Batch Request Builder
+///
+/// A request builder that packs multiple calls of another builder into a single
+/// JSON-RPC batch request.
+pub struct BatchRequestBuilder {
+    inner: Box<dyn RequestBuilder>,
+    size: usize,
+    signatures: Vec<Signature>,
+    fee_tiers: Vec<Option<u64>>,
+    transactions: Vec<VersionedTransaction>,
+    payer: Option<Pubkey>,
+    starved: bool,
+    undelegated: Vec<Vec<Pubkey>>,
+}
+
+impl RequestBuilder for BatchRequestBuilder {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+    fn build(&mut self, id: u64) -> Request<String> {
+        self.signatures.clear();
+        self.fee_tiers.clear();
+        self.transactions.clear();
+        self.payer = None;
+        self.starved = false;
+        self.undelegated.clear();
+        let calls: Vec<String> = (id..id + self.size as u64)
+            .map(|id| {
+                let call = self.inner.build(id).into_body();
+                self.signatures.extend(self.inner.signature());
+                self.fee_tiers.extend(self.inner.fee_tiers());
+                self.transactions.extend(self.inner.transactions());
+                self.payer = self.payer.or(self.inner.payer());
+                self.starved |= self.inner.starved();
+                self.undelegated.extend(self.inner.undelegated());
+                call
+            })
+            .collect();
+        Request::new(payload::batch(&calls))
+    }
+    fn signature(&self) -> Option<Signature> {
+        self.signatures.last().copied()
+    }
+    fn signatures(&self) -> Vec<Signature> {
+        self.signatures.clone()
+    }
+    fn fee_tier(&self) -> Option<u64> {
+        self.fee_tiers.last().copied().flatten()
+    }
+    fn fee_tiers(&self) -> Vec<Option<u64>> {
+        self.fee_tiers.clone()
+    }
+    fn payer(&self) -> Option<Pubkey> {
+        self.payer
+    }
+    fn transactions(&self) -> Vec<VersionedTransaction> {
+        self.transactions.clone()
+    }
+    fn starved(&self) -> bool {
+        self.starved
+    }
+    fn undelegated(&self) -> Vec<Vec<Pubkey>> {
+        self.undelegated.clone()
+    }
+    fn batch_size(&self) -> usize {
+        self.size
+    }
+    fn accounts(&self) -> Vec<Pubkey> {
+        self.inner.accounts()
+    }
+    fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply> {
+        self.inner.extractor()
+    }
+}
+
+/// # Workload RNG
+///
+/// Creates the random number generator of the workload of the benchmark thread with the
+/// given index, derived from the configured seed, or from the OS entropy if not seeded.
+pub fn workload_rng(config: &Config, thread: usize) -> StdRng {
+    match config.benchmark.seed {
+        Some(seed) => StdRng::seed_from_u64(seed.wrapping_add(thread as u64)),
+        None => StdRng::from_entropy(),
+    }
+}
+
+/// # Check Lookup Table
+///
+/// Verifies that the lookup table of the base payer exists on the base chain, and
+/// holds its PDAs in the order of their seeds, which the v0 transactions of the
+/// benchmark rely on. The check is skipped if the mode sends no transactions.
+pub async fn check_lookup_table(config: &Config, base: Pubkey) -> BenchResult<()> {
+    let transacts = match &config.benchmark.mode {
+        BenchMode::Mixed(modes) => modes.iter().any(|m| m.mode.is_transaction()),
+        mode => mode.is_transaction(),
+    };
+    if config.benchmark.transaction_version != TransactionVersion::V0 || !transacts {
+        return Ok(());
+    }
+    let tables = LookupTables::load(config)?;
+    let table = *tables
+        .0
+        .get(&base)
+        .ok_or("no lookup table for the payer, run `assist prepare` first")?;
+    let mut chain = Connection::chain(&config.connection).await?;
+    let payload = payload::get_account_info(
+        table,
+        AccountEncoding::Base64,
+        config.confirmations.commitment,
+        1,
+    );
+    let addresses = chain
+        .send(Request::new(payload), lookup_table_extractor)
+        .resolve()
+        .await?;
+    let space = config.data.account_size as u32;
+    let accounts: Vec<Pubkey> = (1..=config.benchmark.accounts_count)
+        .map(|seed| derive_pda(base, space, seed, config.authority).0)
+        .collect();
+    if addresses != Some(accounts) {
+        let error = format!(
+            "lookup table {table} doesn't match the accounts of {base}, re-run `assist prepare`"
+        );
+        return Err(error.into());
+    }
+    Ok(())
+}
+
+/// # Make Request Builder
+///
+/// Creates the request builder for the given benchmark mode, packing its calls
+/// into batch requests if configured in the mode options. All the random choices
+/// of the builder are derived from `rng`, so that a seeded workload is reproducible.
+/// The accounts undelegated by the builder are re-delegated by `redelegation`.
 pub fn make_builder(
     mode: &BenchMode,
     config: &Config,
     signers: Vec<Keypair>,
     blockhash_provider: BlockHashProvider,
-) -> Box<dyn RequestBuilder> {
+    redelegation: Option<&RedelegationManager>,
+    rng: &mut StdRng,
+) -> BenchResult<Box<dyn RequestBuilder>> {
+    let builder =
+        make_single_builder(mode, config, signers, blockhash_provider, redelegation, rng)?;
+    let size = config.benchmark.mode_options(mode).batch_size();
+    if size == 1 {
+        return Ok(builder);
+    }
+    Ok(Box::new(BatchRequestBuilder {
+        inner: builder,
+        size,
+        signatures: Vec::with_capacity(size),
+        fee_tiers: Vec::with_capacity(size),
+        transactions: Vec::with_capacity(size),
+        payer: None,
+        starved: false,
+        undelegated: Vec::with_capacity(size),
+    }))
+}
+
+/// Creates the request builder for the given benchmark mode, sending a call per request.
+fn make_single_builder(
+    mode: &BenchMode,
+    config: &Config,
+    signers: Vec<Keypair>,
+    blockhash_provider: BlockHashProvider,
+    redelegation: Option<&RedelegationManager>,
+    rng: &mut StdRng,
+) -> BenchResult<Box<dyn RequestBuilder>> {
     let base = signers
         .first()
         .expect("should have at least 1 payer")
         .pubkey();
     let space = config.data.account_size as u32;
     let encoding = config.data.account_encoding;
+    let commitment = config.confirmations.commitment;
     let accounts: Vec<Pubkey> = (1..=config.benchmark.accounts_count)
         .map(|seed| derive_pda(base, space, seed, config.authority).0)
         .collect();
-    match mode {
+    let options = config.benchmark.mode_options(mode);
+    // The RPC requests have no payers, so they are never partitioned
+    let rpc_selector = |rng: &mut StdRng| {
+        let selection = options
+            .account_selection
+            .unwrap_or(AccountSelection::Sequential);
+        let rng = StdRng::seed_from_u64(rng.gen());
+        AccountSelector::new(accounts.clone(), selection, 1, rng)
+    };
+    let builder: Box<dyn RequestBuilder> = match mode {
         BenchMode::GetAccountInfo => Box::new(RpcRequestBuilder::new(
-            accounts,
-            move |pk, id| payload::get_account_info(pk, encoding, id),
+            rpc_selector(rng),
+            move |pk, id| payload::get_account_info(pk, encoding, commitment, id),
             "GetAccountInfo",
         )),
-        BenchMode::GetMultipleAccounts => {
-            Box::new(GetMultipleAccountsRequestBuilder { accounts, encoding })
-        }
-        BenchMode::GetBalance => Box::new(RpcRequestBuilder::new(
+        BenchMode::GetMultipleAccounts => Box::new(GetMultipleAccountsRequestBuilder {
             accounts,
-            payload::get_balance,
+            encoding,
+            commitment,
+        }),
+        BenchMode::GetBalance => Box::new(RpcRequestBuilder::new(
+            rpc_selector(rng),
+            move |pk, id| payload::get_balance(pk, commitment, id),
             "GetBalance",
         )),
         BenchMode::GetTokenAccountBalance => Box::new(RpcRequestBuilder::new(
-            accounts,
-            payload::get_token_account_balance,
+            rpc_selector(rng),
+            move |pk, id| payload::get_token_account_balance(pk, commitment, id),
             "GetTokenAccountBalance",
         )),
         BenchMode::Mixed(modes) => {
@@ -233,27 +534,120 @@ pub fn make_builder(
                 .map(|m| {
                     let signers = signers.iter().map(|k| k.insecure_clone()).collect();
                     let blockhash = blockhash_provider.clone();
-                    (make_builder(&m.mode, config, signers, blockhash), m.weight)
+                    let builder =
+                        make_builder(&m.mode, config, signers, blockhash, redelegation, rng)?;
+                    Ok((builder, m.weight))
                 })
+                .collect::<BenchResult<Vec<_>>>()?
+                .into_iter()
                 .unzip();
             let distribution = WeightedIndex::new(weights).unwrap();
-            let rng = thread_rng();
             Box::new(MixedRequestBuilder {
                 providers,
                 distribution,
-                rng,
+                rng: StdRng::seed_from_u64(rng.gen()),
                 last_name: "",
                 last_index: 0,
             })
         }
         // Handle TPS modes by creating a TransactionRequestBuilder
-        mode => Box::new(TransactionRequestBuilder {
-            provider: crate::transaction::make_provider(mode, accounts),
+        mode => {
+            // The v0 transactions reference the PDAs through the lookup table of the base payer
+            let tables = match config.benchmark.transaction_version {
+                TransactionVersion::Legacy => Vec::new(),
+                TransactionVersion::V0 => {
+                    let tables = LookupTables::load(config)?;
+                    let key = *tables
+                        .0
+                        .get(&base)
+                        .ok_or("no lookup table for the payer, run `assist prepare` first")?;
+                    let addresses = accounts.clone();
+                    vec![AddressLookupTableAccount { key, addresses }]
+                }
+            };
+            let selection = options.account_selection.unwrap_or_default();
+            let payers: Vec<Pubkey> = signers.iter().map(Signer::pubkey).collect();
+            let provider = make_provider(mode, accounts, selection, &payers, redelegation, rng);
+            let mut builder = TransactionRequestBuilder {
+                provider,
+                signers,
+                blockhash_provider,
+                signature: None,
+                transaction: None,
+                payer: None,
+                options: SendOptions::new(config),
+                budget: ComputeBudget::new(&options, StdRng::seed_from_u64(rng.gen())),
+                tables,
+                rng: StdRng::seed_from_u64(rng.gen()),
+            };
+            builder.check_size()?;
+            Box::new(builder)
+        }
+    };
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::make_provider;
+    use core::{config::ModeOptions, types::TransactionEncoding};
+
+    #[test]
+    fn starved_batch_returns_its_accounts() {
+        let accounts: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let redelegation = RedelegationManager::detached(accounts.clone());
+        redelegation.start();
+        let signers = vec![Keypair::new()];
+        let payers = [signers[0].pubkey()];
+        let mode = BenchMode::CommitAndUndelegate {
+            accounts_per_transaction: 2,
+        };
+        let mut rng = StdRng::seed_from_u64(42);
+        let provider = make_provider(
+            &mode,
+            accounts.clone(),
+            AccountSelection::Uniform,
+            &payers,
+            Some(&redelegation),
+            &mut rng,
+        );
+        let inner = TransactionRequestBuilder {
+            provider,
             signers,
-            blockhash_provider,
+            blockhash_provider: BlockHashProvider::fixed(Hash::default()),
             signature: None,
-            preflight: config.benchmark.preflight_check,
-            rng: thread_rng(),
-        }),
+            transaction: None,
+            payer: None,
+            options: SendOptions {
+                preflight: false,
+                preflight_commitment: Commitment::Processed,
+                encoding: TransactionEncoding::Base64,
+                max_retries: None,
+                min_context_slot: None,
+            },
+            budget: ComputeBudget::new(&ModeOptions::default(), StdRng::seed_from_u64(1)),
+            tables: Vec::new(),
+            rng: StdRng::seed_from_u64(2),
+        };
+        let mut builder = BatchRequestBuilder {
+            inner: Box::new(inner),
+            size: 3,
+            signatures: Vec::new(),
+            fee_tiers: Vec::new(),
+            transactions: Vec::new(),
+            payer: None,
+            starved: false,
+            undelegated: Vec::new(),
+        };
+
+        // The first two calls check out all the accounts, which starves the third one
+        builder.build(1);
+        assert!(builder.starved());
+        assert!(accounts.iter().all(|pk| redelegation.cycling(pk)));
+        redelegation.hold_back(&builder.undelegated());
+        assert!(accounts.iter().all(|pk| !redelegation.cycling(pk)));
+        let stats = redelegation.stats();
+        assert_eq!((stats.undelegated, stats.starved), (0, 1));
     }
 }
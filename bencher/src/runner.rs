@@ -1,35 +1,42 @@
 use crate::{
     blockhash::BlockHashProvider,
-    confirmation::{Confirmations, ConfirmationsDB, EventConfirmer},
-    extractor::{account_update_extractor, signature_status_extractor_ws},
-    http::{Connection, ConnectionPool},
+    confirmation::{ConfirmationStats, Confirmations, ConfirmationsDB, EventConfirmer},
+    extractor::{account_update_extractor, signature_status_extractor_ws, Reply},
+    http::{Connection, ConnectionPool, RpcError},
     payload,
+    payload::SendOptions,
+    poller::SignatureStatusPoller,
     rate::RateManager,
-    requests::{make_builder, RequestBuilder},
+    redelegation::RedelegationManager,
+    requests::{check_lookup_table, make_builder, workload_rng, RequestBuilder},
+    trace::{ReplayRequestBuilder, TraceRecorder},
     transfer::TransferManager,
-    websocket::{Subscription, WebsocketPool},
+    websocket::{Subscription, WebsocketPool, WebsocketStatsDB},
     BenchResult, ShutDown, ShutDownSender,
 };
 use core::{
     config::Config,
-    stats::{BenchStatistics, ObservationsStats},
+    stats::{
+        BenchStatistics, EndpointStats, ErrorStats, ObservationsStats, RedelegationStats,
+        TierStats, TimelineBucket, WebsocketStats,
+    },
+    types::DynError,
 };
 use keypair::Keypair;
-use signer::EncodableKey;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use signature::Signature;
+use signer::{EncodableKey, Signer};
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
     rc::Rc,
     sync::{
         atomic::{AtomicU64, Ordering},
         Arc,
     },
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::{sync::oneshot, time::timeout};
-
-/// Timeout for account update confirmations.
-/// Most updates arrive <500ms; 3s handles network delays + retries.
-const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(3);
+use tokio::{sync::oneshot, task::JoinSet, time::timeout};
 
 /// # Bench Runner
 ///
@@ -41,48 +48,79 @@ pub struct BenchRunner {
     request_builder: Box<dyn RequestBuilder>,
     /// A pool of HTTP connections to the ephemeral node.
     ephem: ConnectionPool,
-    /// A pool of WebSocket connections for signature subscriptions.
+    /// A pool of WebSocket connections to each ephemeral endpoint for signature subscriptions.
     signatures_websocket: WebsocketPool<bool>,
+    /// The pollers for signature statuses at each ephemeral endpoint, if confirmations
+    /// by polling are enabled.
+    signature_pollers: Vec<SignatureStatusPoller>,
     /// A database for tracking account update confirmations.
     account_confirmations: ConfirmationsDB<u64>,
     /// A database for tracking signature confirmations.
     signature_confirmations: ConfirmationsDB<bool>,
     /// A map of databases for tracking the delivery of each type of request.
     delivery_confirmations: HashMap<&'static str, ConfirmationsDB<()>>,
+    /// Database for tracking the delivery of requests to each ephemeral endpoint.
+    endpoint_confirmations: HashMap<usize, ConfirmationsDB<()>>,
+    /// Databases for tracking the signature confirmations at each ephemeral endpoint.
+    endpoint_signatures: HashMap<usize, ConfirmationsDB<()>>,
+    /// Databases for tracking the landing of transactions in each priority fee tier.
+    tier_confirmations: HashMap<u64, ConfirmationsDB<()>>,
+    /// The number of transactions sent in each priority fee tier.
+    tier_sent: HashMap<u64, u64>,
+    /// Counters for failed requests and confirmation timeouts.
+    errors: ErrorsDB,
+    /// Counters for the health of the WebSocket connections.
+    websocket_stats: WebsocketStatsDB,
     /// A manager for periodically transferring lamports to trigger account cloning.
     transfer_manager: TransferManager,
+    /// A manager for re-delegating the accounts, if the mode undelegates them.
+    redelegation: Option<RedelegationManager>,
     /// A manager for controlling the rate of requests per second
     rate_manager: RateManager,
+    /// A recorder of the generated requests, if recording to a trace is enabled.
+    recorder: Option<TraceRecorder>,
     /// The benchmark configuration.
     config: Config,
     /// A mechanism for gracefully shutting down the benchmark.
     shutdown: ShutDown,
     /// Shared benchmark progress indicator value
     progress: Arc<AtomicU64>,
+    /// The start of the benchmark, from which the timeline seconds are counted.
+    started: Instant,
+    /// The ID of the next call, each request takes up one ID per call in it.
+    next_id: u64,
 }
 
 type AcctRx = Option<oneshot::Receiver<u64>>;
 type SigRx = Option<oneshot::Receiver<bool>>;
 
-/// Helper function for subscribing with synchronization enabled.
+/// Helper function for subscribing to a confirmation.
 /// Returns a receiver that will be notified when the confirmation arrives.
-fn subscribe_with_sync<V>(id: u64, confirmations: &ConfirmationsDB<V>) -> oneshot::Receiver<V> {
+fn subscribe<V>(
+    id: u64,
+    confirmations: &ConfirmationsDB<V>,
+    intended: Instant,
+) -> oneshot::Receiver<V> {
     let (tx, rx) = oneshot::channel();
-    confirmations.borrow_mut().track(id, Some(tx));
+    confirmations.borrow_mut().track(id, Some(tx), intended);
     rx
 }
 
-/// Helper function for subscribing without synchronization.
-/// Tracks the confirmation but doesn't wait for it.
-fn subscribe_no_sync<V>(id: u64, confirmations: &ConfirmationsDB<V>) {
-    confirmations.borrow_mut().track(id, None);
+/// Helper function for creating a database, which tracks the delivery or the landing
+/// of requests, with the timeline seconds counted from the given origin.
+fn tracker(origin: Instant) -> ConfirmationsDB<()> {
+    let db = Confirmations::new().0;
+    db.borrow_mut().set_origin(origin);
+    db
 }
 
 impl BenchRunner {
     /// # New Bench Runner
     ///
-    /// Creates a new `BenchRunner` instance, initializing all the necessary components.
+    /// Creates a new `BenchRunner` instance for the benchmark thread with the given index,
+    /// initializing all the necessary components.
     pub async fn new(
+        index: usize,
         signers: Vec<Keypair>,
         config: Config,
         progress: Arc<AtomicU64>,
@@ -91,23 +129,41 @@ impl BenchRunner {
         let ephem_conn = Connection::new(
             &config.connection.ephem_url,
             config.connection.http_connection_type,
+            &config.connection,
         )
         .await?;
         // Initialize the shutdown signal handler.
         let shutdown = ShutDownSender::init();
 
         // Create a new blockhash provider to keep the blockhash updated.
-        let blockhash_provider = BlockHashProvider::new(ephem_conn, shutdown.listener()).await?;
+        let blockhash_provider = BlockHashProvider::new(
+            ephem_conn,
+            config.confirmations.commitment,
+            shutdown.listener(),
+        )
+        .await?;
+        // The random choices of the workload, including the endpoints of the requests,
+        // are derived from the seed, if configured.
+        let mut rng = workload_rng(&config, index);
         // Create a new pool of HTTP connections to the ephemeral node.
-        let ephem = ConnectionPool::new(&config.connection).await?;
+        let pool_rng = StdRng::seed_from_u64(rng.gen());
+        let ephem = ConnectionPool::new(&config.connection, pool_rng).await?;
         // Create a new rate manager to control the request rate.
-        let rate_manager = RateManager::new(config.benchmark.concurrency, config.benchmark.rate);
+        let rate_manager = RateManager::new(
+            config.benchmark.concurrency,
+            config.benchmark.rate,
+            config.benchmark.rate_profile.clone(),
+        );
 
-        // Create a new pool of WebSocket connections for signature subscriptions.
+        // Create a new pool of WebSocket connections for signature subscriptions, which
+        // are sent to the endpoint of the transaction, as the other ones might not see it.
+        let websocket_stats = WebsocketStatsDB::default();
         let signatures_websocket = WebsocketPool::new(
             &config.connection,
+            &config.connection.ephem_ws_urls(),
             signature_status_extractor_ws,
             shutdown.clone(),
+            websocket_stats.clone(),
         )
         .await?;
 
@@ -121,32 +177,95 @@ impl BenchRunner {
         let signature_confirmations = signatures_confirmer.db.clone();
         tokio::task::spawn_local(signatures_confirmer.confirm_by_id());
 
-        // The request builder creates the transactions or RPC requests to be sent.
-        let request_builder = make_builder(
-            &config.benchmark.mode,
-            &config,
-            signers,
-            blockhash_provider.clone(),
-        );
+        // These pollers will track signature confirmations via `getSignatureStatuses`,
+        // at the endpoint each transaction was sent to.
+        let mut signature_pollers = Vec::new();
+        if config.confirmations.get_signature_status {
+            for url in config.connection.ephem_urls() {
+                let con = Connection::new(
+                    &url,
+                    config.connection.http_connection_type,
+                    &config.connection,
+                )
+                .await?;
+                let tx = signature_confirmations.borrow().tx.clone();
+                let interval = config.confirmations.signature_status_interval();
+                signature_pollers.push(SignatureStatusPoller::new(
+                    con,
+                    tx,
+                    interval,
+                    config.confirmations.signature_commitment(),
+                    config.confirmations.signature_timeout(),
+                    shutdown.listener(),
+                ));
+            }
+        }
+
+        let base = signers
+            .first()
+            .expect("should have at least 1 payer")
+            .pubkey();
+        // The v0 transactions, also the replayed ones, reference the PDAs through the
+        // lookup table, which might have gone stale since it was created.
+        check_lookup_table(&config, base).await?;
+
+        // The accounts undelegated by the benchmark, or by the replay of its trace, are
+        // delegated again on the base chain.
+        let redelegation = if config.benchmark.mode.undelegates() {
+            Some(RedelegationManager::new(&config, base, &shutdown).await?)
+        } else {
+            None
+        };
+
+        // The request builder creates the transactions or RPC requests to be sent,
+        // or re-sends the ones recorded to the trace when replaying it.
+        let request_builder: Box<dyn RequestBuilder> = match &config.trace {
+            Some(trace) if trace.replay => Box::new(ReplayRequestBuilder::load(
+                trace,
+                index,
+                signers,
+                blockhash_provider.clone(),
+                SendOptions::new(&config),
+                redelegation.clone(),
+            )?),
+            _ => make_builder(
+                &config.benchmark.mode,
+                &config,
+                signers,
+                blockhash_provider.clone(),
+                redelegation.as_ref(),
+                &mut rng,
+            )?,
+        };
 
         let accounts = request_builder.accounts();
+        let recorder = match &config.trace {
+            Some(trace) if trace.record && !trace.replay => {
+                Some(TraceRecorder::new(trace, index, accounts.clone())?)
+            }
+            _ => None,
+        };
         if config.confirmations.subscribe_to_accounts {
-            // Create a new pool of WebSocket connections for account update subscriptions.
+            // Create a new pool of WebSocket connections for account update subscriptions,
+            // which are observed at `ephem_url` alone, to be confirmed once.
             let mut accounts_websocket = WebsocketPool::new(
                 &config.connection,
+                &[config.connection.ephem_ws_url()],
                 account_update_extractor,
                 shutdown.clone(),
+                websocket_stats.clone(),
             )
             .await?;
             let encoding = config.data.account_encoding;
+            let commitment = config.confirmations.account_commitment();
             // Subscribe to account updates for all the accounts used in the benchmark.
             for (id, pk) in accounts.iter().enumerate() {
                 let id = id as u64;
                 let tx = account_confirmations.borrow().tx.clone();
-                let con = accounts_websocket.connection();
+                let con = accounts_websocket.connection(0);
                 let sub = Subscription {
                     tx,
-                    payload: payload::account_subscription(*pk, encoding, id),
+                    payload: payload::account_subscription(*pk, encoding, commitment, id),
                     oneshot: false,
                     id,
                 };
@@ -164,142 +283,397 @@ impl BenchRunner {
             request_builder,
             ephem,
             signatures_websocket,
+            signature_pollers,
             account_confirmations,
             signature_confirmations,
             delivery_confirmations: HashMap::new(),
+            endpoint_confirmations: HashMap::new(),
+            endpoint_signatures: HashMap::new(),
+            tier_confirmations: HashMap::new(),
+            tier_sent: HashMap::new(),
+            errors: Rc::default(),
+            websocket_stats,
             rate_manager,
+            recorder,
             transfer_manager,
+            redelegation,
             config,
             shutdown,
             progress,
+            started: Instant::now(),
+            next_id: 0,
         })
     }
 
     /// # Run Benchmark
     ///
-    /// Starts the benchmark, sending requests at the configured rate.
+    /// Starts the benchmark, sending requests at the configured rate, until either
+    /// the configured number of iterations is sent or the configured duration elapses.
     pub async fn run(mut self) -> BenchResults {
-        self.rate_manager.reset();
-        for i in 0..self.config.benchmark.iterations {
+        let iterations = self.config.benchmark.iterations.unwrap_or(u64::MAX);
+        self.started = Instant::now();
+        let deadline = self.config.benchmark.duration().map(|d| self.started + d);
+        self.rate_manager.start(self.started);
+        if let Some(redelegation) = &self.redelegation {
+            redelegation.start();
+        }
+        self.account_confirmations
+            .borrow_mut()
+            .set_origin(self.started);
+        self.signature_confirmations
+            .borrow_mut()
+            .set_origin(self.started);
+        for _ in 0..iterations {
             // Check for shutdown signal
             if crate::SHUTDOWN.load(Ordering::Relaxed) {
                 tracing::info!("Shutdown requested, stopping benchmark gracefully...");
                 break;
             }
+            // Check whether the benchmark has run for the configured duration
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                break;
+            }
+            // Check whether the replayed trace has run out of requests
+            if self.request_builder.exhausted() {
+                break;
+            }
 
             // This will trigger an account update on the main chain, which in turn
             // will trigger an account clone on the Ephemeral Rollup.
             self.transfer_manager.transfer();
 
-            self.step(i).await;
+            self.step().await;
             // report progress
             self.progress.fetch_add(1, Ordering::Relaxed);
         }
 
+        // Wait for the undelegated accounts to be delegated again, before the shutdown
+        if let Some(redelegation) = &self.redelegation {
+            redelegation.drain().await;
+        }
+        let (rate, timeline) = self.rate_manager.stats();
         BenchResults {
             config: self.config,
             delivery_confirmations: self.delivery_confirmations,
+            endpoint_confirmations: self.endpoint_confirmations,
+            endpoint_signatures: self.endpoint_signatures,
+            tier_confirmations: self.tier_confirmations,
+            tier_sent: self.tier_sent,
             account_confirmations: self.account_confirmations,
             signature_confirmations: self.signature_confirmations,
-            rate: self.rate_manager.stats(),
+            errors: self.errors.take(),
+            websocket: self.websocket_stats.take(),
+            rate,
+            timeline,
+            redelegation: self.redelegation.map(|r| r.stats()).unwrap_or_default(),
         }
     }
 
     #[inline(always)]
-    async fn step(&mut self, id: u64) {
-        // Get a connection from the pool.
-        let mut con = self.ephem.connection().await.expect("connection closed");
-        // Get a permit from the rate manager to send a request.
-        let permit = self.rate_manager.tick().await;
+    async fn step(&mut self) {
+        // Get a permit from the rate manager to send a request, along with the
+        // time when the request was scheduled to be sent, which is the point
+        // to measure the latency from, without coordinated omission.
+        let (permit, intended) = match self.request_builder.due() {
+            Some(at) => self.rate_manager.tick_at(at).await,
+            None => self.rate_manager.tick().await,
+        };
 
-        // Build the request.
+        // Build the request, which takes up a consecutive ID for each of its calls.
+        let id = self.next_id;
         let request = self.request_builder.build(id);
+        let undelegated = self.request_builder.undelegated();
+        // The request is held back if all the accounts of a call are being re-delegated,
+        // and the accounts checked out by its other calls return to the pool
+        if self.request_builder.starved() {
+            if let Some(redelegation) = &self.redelegation {
+                redelegation.hold_back(&undelegated);
+            }
+            return;
+        }
+        if let Some(recorder) = &mut self.recorder {
+            let offset = intended.saturating_duration_since(self.started);
+            let recorded = recorder.record(offset, &*self.request_builder, request.body());
+            if let Err(error) = recorded {
+                tracing::warn!(%error, "failed to record the request, stopping the recording");
+                self.recorder = None;
+            }
+        }
+        let batch = self.request_builder.batch_size() as u64;
+        self.next_id += batch;
         let request_name = self.request_builder.name();
         let extractor = self.request_builder.extractor();
 
+        // Get a connection to the endpoint selected for this request from the pool.
+        let payer = self.request_builder.payer();
+        let mut con = self
+            .ephem
+            .connection(payer.as_ref())
+            .await
+            .expect("connection closed");
+        let endpoint = con.endpoint();
+
         // Get the confirmation database for this request type.
+        let started = self.started;
         let delivery = self
             .delivery_confirmations
             .entry(request_name)
-            .or_insert_with(|| Confirmations::new().0)
+            .or_insert_with(|| tracker(started))
+            .clone();
+        let endpoint_delivery = self
+            .endpoint_confirmations
+            .entry(endpoint)
+            .or_insert_with(|| tracker(started))
             .clone();
+        // The timeline second in which the request is sent.
+        let second = started.elapsed().as_secs();
 
         let response = con.send(request, extractor);
         drop(con);
-        // Subscribe to confirmations if needed.
-        let (account_rx, signature_rx) = self.subscribe_if_needed(id).await;
-
-        // Track the delivery of the request.
-        delivery.borrow_mut().track(id, None);
+        // Subscribe to confirmations if needed, and track the delivery of every call.
+        let signatures = self.request_builder.signatures();
+        let fee_tiers = self.request_builder.fee_tiers();
+        let mut calls = Vec::with_capacity(batch as usize);
+        for (index, id) in (id..id + batch).enumerate() {
+            let signature = signatures.get(index).copied();
+            let (account_rx, signature_rx) = self
+                .subscribe_if_needed(id, signature, endpoint, intended)
+                .await;
+            delivery.borrow_mut().track(id, None, intended);
+            endpoint_delivery.borrow_mut().track(id, None, intended);
+            // Track the landing of the transaction at its endpoint, if confirmed, and
+            // within its priority fee tier.
+            let mut landing = Vec::new();
+            if signature_rx.is_some() {
+                landing.push(
+                    self.endpoint_signatures
+                        .entry(endpoint)
+                        .or_insert_with(|| tracker(started))
+                        .clone(),
+                );
+            }
+            if let Some(tier) = fee_tiers.get(index).copied().flatten() {
+                *self.tier_sent.entry(tier).or_default() += 1;
+                landing.push(
+                    self.tier_confirmations
+                        .entry(tier)
+                        .or_insert_with(|| tracker(started))
+                        .clone(),
+                );
+            }
+            for db in &landing {
+                db.borrow_mut().track(id, None, intended);
+            }
+            let accounts = undelegated.get(index).cloned().unwrap_or_default();
+            calls.push((id, signature, account_rx, signature_rx, landing, accounts));
+        }
 
         // Spawn a new task to handle the response and confirmations.
         let shutdown = self.shutdown.clone();
         let total_sync = self.config.confirmations.enforce_total_sync;
+        let account_timeout = self.config.confirmations.account_timeout();
+        let signature_timeout = self.config.confirmations.signature_timeout();
         let account_confirmations = self.account_confirmations.clone();
+        let signature_confirmations = self.signature_confirmations.clone();
+        let errors = self.errors.clone();
+        let redelegation = self.redelegation.clone();
         tokio::task::spawn_local(async move {
-            match response.resolve().await {
-                Ok(Some(false)) => tracing::warn!(id, "request failed to be executed"),
-                Err(error) => tracing::error!(id, %error, "request failed to be delivered"),
-                _ => (),
+            // Calls which are absent from the response are counted as failed
+            let (mut outcomes, missing) = if batch == 1 {
+                let outcome = outcome(id, response.resolve().await);
+                (HashMap::from([(id, outcome)]), String::new())
+            } else {
+                match response.resolve_batch().await {
+                    Ok(results) => {
+                        let outcomes = results
+                            .into_iter()
+                            .map(|(id, result)| (id, outcome(id, result)))
+                            .collect();
+                        (outcomes, "missing from batch response".into())
+                    }
+                    Err(error) => (HashMap::new(), describe(id, error)),
+                }
+            };
+            // Observe the delivery of every call.
+            let mut delivered = Vec::with_capacity(calls.len());
+            for (id, signature, account_rx, signature_rx, landing, accounts) in calls {
+                delivery.borrow_mut().observe(id, ());
+                endpoint_delivery.borrow_mut().observe(id, ());
+                let outcome = outcomes.remove(&id).unwrap_or_else(|| Err(missing.clone()));
+                match outcome {
+                    Ok(reply) => {
+                        // The confirmations are subscribed to by the signature the transaction was signed with
+                        let mismatch = match (reply, signature) {
+                            (Reply::Signature(returned), Some(expected)) => {
+                                let mismatch = returned != expected;
+                                if mismatch {
+                                    tracing::warn!(
+                                        id,
+                                        %returned,
+                                        %expected,
+                                        "rpc returned a different signature"
+                                    );
+                                }
+                                mismatch
+                            }
+                            _ => false,
+                        };
+                        errors
+                            .borrow_mut()
+                            .record(request_name, endpoint, second, |e| {
+                                e.delivered += 1;
+                                e.signature_mismatches += u64::from(mismatch);
+                            });
+                        delivered.push((id, account_rx, signature_rx, landing, accounts));
+                    }
+                    Err(description) => {
+                        errors
+                            .borrow_mut()
+                            .record(request_name, endpoint, second, |e| {
+                                e.rpc_error(description.clone())
+                            });
+                        // No confirmations will follow a failed call
+                        account_confirmations.borrow_mut().remove(id);
+                        signature_confirmations.borrow_mut().remove(id);
+                        for landing in &landing {
+                            landing.borrow_mut().remove(id);
+                        }
+                        // Nor will the undelegation of its accounts
+                        if let Some(redelegation) = &redelegation {
+                            redelegation.release(&accounts);
+                        }
+                    }
+                }
             }
-            // Observe the delivery of the request.
-            delivery.borrow_mut().observe(id, ());
             // If total sync is not enforced, drop the permit to allow other requests to be sent.
             if !total_sync {
                 drop(permit);
             }
-            // Wait for the account update confirmation, if subscribed.
-            if let Some(rx) = account_rx {
-                if timeout(CONFIRMATION_TIMEOUT, rx).await.is_err() {
-                    account_confirmations.borrow_mut().remove(id);
+            // The confirmations of all the calls are awaited concurrently, so that each one
+            // is observed as soon as it arrives, within its own timeout.
+            let mut confirmations = JoinSet::new();
+            for (id, account_rx, signature_rx, landing, accounts) in delivered {
+                // Wait for the account update confirmation, if subscribed.
+                let account_confirmations = account_confirmations.clone();
+                let account_errors = errors.clone();
+                // Both futures tell whether the call failed.
+                let account = async move {
+                    let Some(rx) = account_rx else {
+                        return false;
+                    };
+                    let failed = timeout(account_timeout, rx).await.is_err();
+                    if failed {
+                        account_confirmations.borrow_mut().remove(id);
+                        account_errors
+                            .borrow_mut()
+                            .record(request_name, endpoint, second, |e| {
+                                e.account_update_timeouts += 1
+                            });
+                    };
+                    failed
                 };
+                // Wait for the signature confirmation, if subscribed.
+                let signature_confirmations = signature_confirmations.clone();
+                let signature_errors = errors.clone();
+                let redelegation = redelegation.clone();
+                let signature = async move {
+                    let mut landed = false;
+                    let mut failed = false;
+                    if let Some(rx) = signature_rx {
+                        match timeout(signature_timeout, rx).await {
+                            Ok(Ok(true)) => landed = true,
+                            Ok(Err(_)) => (),
+                            Ok(Ok(false)) => {
+                                failed = true;
+                                signature_errors.borrow_mut().record(
+                                    request_name,
+                                    endpoint,
+                                    second,
+                                    |e| e.executed_with_error += 1,
+                                );
+                                // A failed undelegation leaves the accounts delegated
+                                if let Some(redelegation) = &redelegation {
+                                    redelegation.release(&accounts);
+                                }
+                            }
+                            Err(_) => {
+                                failed = true;
+                                signature_confirmations.borrow_mut().remove(id);
+                                signature_errors.borrow_mut().record(
+                                    request_name,
+                                    endpoint,
+                                    second,
+                                    |e| e.signature_timeouts += 1,
+                                );
+                            }
+                        }
+                    }
+                    // Only the transactions which executed successfully are counted as
+                    // landed, at the time their signature confirmation arrived.
+                    for landing in &landing {
+                        let mut landing = landing.borrow_mut();
+                        if landed {
+                            landing.observe(id, ());
+                        } else {
+                            landing.remove(id);
+                        }
+                    }
+                    failed
+                };
+                // The call is counted as failed once, whichever ways it failed in
+                let errors = errors.clone();
+                confirmations.spawn_local(async move {
+                    if let (true, _) | (_, true) = tokio::join!(account, signature) {
+                        errors
+                            .borrow_mut()
+                            .record(request_name, endpoint, second, |e| e.failed += 1);
+                    }
+                });
             }
-            // Wait for the signature confirmation, if subscribed.
-            if let Some(rx) = signature_rx {
-                let _ = timeout(CONFIRMATION_TIMEOUT, rx).await;
-            }
+            while confirmations.join_next().await.is_some() {}
             drop(shutdown);
         });
     }
 
-    async fn subscribe_if_needed(&mut self, id: u64) -> (AcctRx, SigRx) {
+    async fn subscribe_if_needed(
+        &mut self,
+        id: u64,
+        signature: Option<Signature>,
+        endpoint: usize,
+        intended: Instant,
+    ) -> (AcctRx, SigRx) {
         // Early return for RPC requests (no signature)
-        let Some(signature) = self.request_builder.signature() else {
+        let Some(signature) = signature else {
             return (None, None);
         };
 
-        let total_sync = self.config.confirmations.enforce_total_sync;
-
         // Setup signature subscription if enabled
         let signature_rx = if self.config.confirmations.subscribe_to_signatures {
-            let con = self.signatures_websocket.connection();
+            let con = self.signatures_websocket.connection(endpoint);
             let tx = self.signature_confirmations.borrow().tx.clone();
             let sub = Subscription {
                 tx,
-                payload: payload::signature_subscription(signature, id),
+                payload: payload::signature_subscription(
+                    signature,
+                    self.config.confirmations.signature_commitment(),
+                    id,
+                ),
                 oneshot: true,
                 id,
             };
             let _ = con.send(sub).await;
-
-            if total_sync {
-                Some(subscribe_with_sync(id, &self.signature_confirmations))
-            } else {
-                subscribe_no_sync(id, &self.signature_confirmations);
-                None
-            }
+            Some(subscribe(id, &self.signature_confirmations, intended))
+        } else if let Some(poller) = self.signature_pollers.get(endpoint) {
+            let rx = subscribe(id, &self.signature_confirmations, intended);
+            poller.poll(id, signature);
+            Some(rx)
         } else {
             None
         };
 
         // Setup account subscription if enabled
         let account_rx = if self.config.confirmations.subscribe_to_accounts {
-            if total_sync {
-                Some(subscribe_with_sync(id, &self.account_confirmations))
-            } else {
-                subscribe_no_sync(id, &self.account_confirmations);
-                None
-            }
+            Some(subscribe(id, &self.account_confirmations, intended))
         } else {
             None
         };
@@ -308,6 +682,72 @@ impl BenchRunner {
     }
 }
 
+/// Returns the reply to a call, if it was delivered, or the description of the error to
+/// record otherwise.
+fn outcome(id: u64, result: BenchResult<Option<Reply>>) -> Result<Reply, String> {
+    match result {
+        Ok(Some(reply)) => Ok(reply),
+        Ok(None) => {
+            tracing::warn!(id, "request failed to be executed");
+            Err("unexpected response".into())
+        }
+        Err(error) => Err(describe(id, error)),
+    }
+}
+
+/// Describes the error of a failed request, telling the errors returned by the RPC
+/// from the failures to deliver the request.
+fn describe(id: u64, error: DynError) -> String {
+    match error.downcast_ref::<RpcError>() {
+        Some(rpc) => {
+            tracing::warn!(id, %rpc, logs = ?rpc.logs, "request was rejected");
+            rpc.to_string()
+        }
+        None => {
+            tracing::error!(id, %error, "request failed to be delivered");
+            format!("delivery failure: {error}")
+        }
+    }
+}
+
+/// A type alias for a reference-counted, interior-mutable `ErrorCounters` struct.
+type ErrorsDB = Rc<RefCell<ErrorCounters>>;
+
+/// # Error Counters
+///
+/// Tracks the outcomes of the requests for each benchmark mode and endpoint, along
+/// with the number of failures by the second in which the request was sent.
+#[derive(Default)]
+struct ErrorCounters {
+    /// The outcome counters for each benchmark mode.
+    modes: HashMap<&'static str, ErrorStats>,
+    /// The outcome counters for each ephemeral endpoint.
+    endpoints: HashMap<usize, ErrorStats>,
+    /// The number of failed requests, by the second since the start of the benchmark.
+    timeline: BTreeMap<u64, u64>,
+}
+
+impl ErrorCounters {
+    /// Records the outcome of a request of the given mode, sent to the given endpoint
+    /// during the given second, accounting for a failed request in the timeline.
+    fn record(
+        &mut self,
+        mode: &'static str,
+        endpoint: usize,
+        second: u64,
+        update: impl Fn(&mut ErrorStats),
+    ) {
+        update(self.endpoints.entry(endpoint).or_default());
+        let stats = self.modes.entry(mode).or_default();
+        let before = stats.failed;
+        update(stats);
+        let failed = stats.failed - before;
+        if failed > 0 {
+            *self.timeline.entry(second).or_default() += failed;
+        }
+    }
+}
+
 /// # Benchmark Results
 ///
 /// Holds the results of the benchmark run, including all collected statistics.
@@ -316,32 +756,117 @@ pub struct BenchResults {
     account_confirmations: ConfirmationsDB<u64>,
     signature_confirmations: ConfirmationsDB<bool>,
     delivery_confirmations: HashMap<&'static str, ConfirmationsDB<()>>,
+    endpoint_confirmations: HashMap<usize, ConfirmationsDB<()>>,
+    endpoint_signatures: HashMap<usize, ConfirmationsDB<()>>,
+    tier_confirmations: HashMap<u64, ConfirmationsDB<()>>,
+    tier_sent: HashMap<u64, u64>,
+    errors: ErrorCounters,
+    websocket: WebsocketStats,
     rate: ObservationsStats,
+    timeline: Vec<TimelineBucket>,
+    redelegation: RedelegationStats,
 }
 
 /// Helper function to finalize a confirmation database's statistics.
 /// Unwraps the Rc and RefCell to get the inner Confirmations struct.
-fn finalize<V: std::fmt::Debug>(db: ConfirmationsDB<V>) -> ObservationsStats {
+fn finalize<V: std::fmt::Debug>(db: ConfirmationsDB<V>) -> ConfirmationStats {
     Rc::try_unwrap(db).unwrap().into_inner().finalize()
 }
 
+/// Helper function to get the timeline bucket for the given second, creating it if needed.
+fn bucket(timeline: &mut BTreeMap<u64, TimelineBucket>, second: u64) -> &mut TimelineBucket {
+    timeline.entry(second).or_insert_with(|| TimelineBucket {
+        second,
+        ..Default::default()
+    })
+}
+
 impl BenchResults {
     /// # Calculate Statistics
     ///
     /// Finalizes the benchmark results and calculates the statistics.
-    pub fn stats(self) -> BenchStatistics {
+    pub fn stats(mut self) -> BenchStatistics {
         let mut request_stats = HashMap::new();
+        let mut corrected_request_stats = HashMap::new();
+        let mut timeline: BTreeMap<u64, TimelineBucket> = self
+            .timeline
+            .into_iter()
+            .map(|bucket| (bucket.second, bucket))
+            .collect();
 
         for (mode_name, confirmations) in self.delivery_confirmations {
-            request_stats.insert(mode_name.to_string(), finalize(confirmations));
+            let stats = finalize(confirmations);
+            for (second, stats) in stats.timeline {
+                bucket(&mut timeline, second)
+                    .request_latency
+                    .insert(mode_name.to_string(), stats);
+            }
+            request_stats.insert(mode_name.to_string(), stats.latency);
+            corrected_request_stats.insert(mode_name.to_string(), stats.corrected);
+        }
+        let signatures = finalize(self.signature_confirmations);
+        for (second, stats) in signatures.timeline {
+            bucket(&mut timeline, second).signature_confirmation_latency = stats;
+        }
+        let accounts = finalize(self.account_confirmations);
+        for (second, stats) in accounts.timeline {
+            bucket(&mut timeline, second).account_update_latency = stats;
+        }
+        for (second, errors) in self.errors.timeline {
+            bucket(&mut timeline, second).errors = errors;
+        }
+        let urls = self.config.connection.ephem_urls();
+        let mut endpoints = HashMap::new();
+        for (index, confirmations) in self.endpoint_confirmations {
+            let signatures = self.endpoint_signatures.remove(&index);
+            let stats = EndpointStats {
+                request_latency: finalize(confirmations).latency,
+                signature_confirmation_latency: signatures
+                    .map(|db| finalize(db).latency)
+                    .unwrap_or_default(),
+                errors: self.errors.endpoints.remove(&index).unwrap_or_default(),
+            };
+            endpoints.insert(urls[index].0.to_string(), stats);
         }
+        let mut tiers: Vec<_> = self
+            .tier_confirmations
+            .into_iter()
+            .map(|(priority_fee, confirmations)| {
+                let latency = finalize(confirmations).latency;
+                TierStats {
+                    priority_fee,
+                    sent: self
+                        .tier_sent
+                        .get(&priority_fee)
+                        .copied()
+                        .unwrap_or_default(),
+                    landed: latency.count as u64,
+                    signature_confirmation_latency: latency,
+                }
+            })
+            .collect();
+        tiers.sort_by_key(|tier| tier.priority_fee);
 
         BenchStatistics {
             configuration: json::to_value(&self.config).unwrap(),
             request_stats,
-            signature_confirmation_latency: finalize(self.signature_confirmations),
-            account_update_latency: finalize(self.account_confirmations),
+            signature_confirmation_latency: signatures.latency,
+            account_update_latency: accounts.latency,
+            corrected_request_stats,
+            corrected_signature_confirmation_latency: signatures.corrected,
+            corrected_account_update_latency: accounts.corrected,
             rps: self.rate,
+            errors: self
+                .errors
+                .modes
+                .into_iter()
+                .map(|(mode, errors)| (mode.to_string(), errors))
+                .collect(),
+            timeline: timeline.into_values().collect(),
+            websocket: self.websocket,
+            endpoints,
+            tiers,
+            redelegation: self.redelegation,
         }
     }
 }
@@ -0,0 +1,124 @@
+//! Saturation search for the maximum sustainable rate.
+//!
+//! Runs a series of short trials at fixed rates, bisecting on the rate, to
+//! find the knee point: the highest rate at which the validator still meets
+//! the configured SLO for latency and error rate.
+
+use core::{
+    config::{Config, SearchSettings},
+    stats::{SearchReport, SearchTrial},
+    types::BenchResult,
+};
+use std::sync::atomic::Ordering;
+
+use keypair::Keypair;
+
+/// # Saturation Search
+///
+/// Bisects on the rate between the configured bounds, running a full benchmark
+/// for each trial, and returns the knee point along with the per-trial statistics.
+pub fn search(config: &Config, keypairs: &[Keypair]) -> BenchResult<SearchReport> {
+    let settings = config
+        .search
+        .clone()
+        .ok_or("the [search] section is required to run a saturation search")?;
+    if settings.min_rate == 0 || settings.min_rate > settings.max_rate {
+        return Err("search rate bounds should satisfy 0 < min-rate <= max-rate".into());
+    }
+    let mut searcher = Searcher {
+        config,
+        keypairs,
+        settings,
+        trials: Vec::new(),
+    };
+    let knee = searcher.bisect();
+    match knee {
+        Some(rate) => tracing::info!(
+            "maximum sustainable rate: {rate} x {} thread(s)",
+            config.parallelism
+        ),
+        None => tracing::warn!("SLO wasn't met even at the lowest rate"),
+    }
+    Ok(SearchReport {
+        knee,
+        trials: searcher.trials,
+    })
+}
+
+/// Keeps the state of the saturation search in between trials.
+struct Searcher<'a> {
+    config: &'a Config,
+    keypairs: &'a [Keypair],
+    settings: SearchSettings,
+    trials: Vec<SearchTrial>,
+}
+
+impl Searcher<'_> {
+    /// Runs the bisection, returning the highest rate which met the SLO.
+    /// Returns early with the best known rate if the search is interrupted.
+    fn bisect(&mut self) -> Option<u32> {
+        let (mut low, mut high) = (self.settings.min_rate, self.settings.max_rate);
+        // The lower bound must pass for the search to make any sense
+        if !self.trial(low)? {
+            return None;
+        }
+        // If the upper bound passes, there's nothing to bisect
+        if self.trial(high)? {
+            return Some(high);
+        }
+        while high - low > self.settings.resolution.max(1) {
+            let middle = low + (high - low) / 2;
+            match self.trial(middle) {
+                Some(true) => low = middle,
+                Some(false) => high = middle,
+                None => break,
+            }
+        }
+        Some(low)
+    }
+
+    /// Runs a single trial at the given rate and records it. Returns whether
+    /// the SLO was met, or `None` if the trial was interrupted by a shutdown.
+    fn trial(&mut self, rate: u32) -> Option<bool> {
+        if crate::SHUTDOWN.load(Ordering::Relaxed) {
+            return None;
+        }
+        tracing::info!(
+            "running {}s trial at {rate} x {} thread(s)",
+            self.settings.trial_duration,
+            self.config.parallelism
+        );
+        let mut config = self.config.clone();
+        config.benchmark.rate = rate;
+        config.benchmark.rate_profile = None;
+        config.benchmark.iterations = None;
+        config.benchmark.duration = Some(self.settings.trial_duration);
+
+        let stats = crate::bench(&config, self.keypairs);
+        if crate::SHUTDOWN.load(Ordering::Relaxed) {
+            return None;
+        }
+
+        let latency95 = stats.slo_latency();
+        let error_rate = stats.error_rate();
+        // The limit in microseconds might overflow i32
+        let passed = i64::from(latency95) <= i64::from(self.settings.max_p95_latency_ms) * 1000
+            && error_rate <= self.settings.max_error_rate;
+        tracing::info!(
+            rate,
+            achieved = stats.rps.median,
+            latency95,
+            error_rate,
+            passed,
+            "trial finished"
+        );
+        self.trials.push(SearchTrial {
+            rate,
+            passed,
+            latency95,
+            error_rate,
+            stats,
+        });
+        Some(passed)
+    }
+}
@@ -0,0 +1,246 @@
+//! Account selection strategies.
+//!
+//! Decides which accounts every request operates on, and thereby the level of lock
+//! contention between the transactions: from none at all, with disjoint partitions
+//! per payer, up to a handful of hot accounts under a highly skewed Zipf distribution.
+
+use core::types::AccountSelection;
+use std::ops::Range;
+
+use pubkey::Pubkey;
+use rand::{rngs::StdRng, seq::SliceRandom};
+
+/// # Account Selector
+///
+/// Selects the accounts of every request according to the configured strategy.
+pub struct AccountSelector {
+    accounts: Vec<Pubkey>,
+    strategy: AccountSelection,
+    /// The accounts along with their Zipfian weights, by rank.
+    weighted: Vec<(Pubkey, f64)>,
+    /// The start of the sliding window of the sequential selection.
+    cursor: usize,
+    /// The number of accounts in every partition of the partitioned selection.
+    partition_size: usize,
+    /// The index of the partition of the current payer.
+    partition: usize,
+    rng: StdRng,
+}
+
+impl AccountSelector {
+    /// # New Account Selector
+    ///
+    /// Creates a selector over the given accounts, which are split into one partition
+    /// per payer, if the selection is partitioned. The accounts are drawn with `rng`.
+    pub fn new(
+        accounts: Vec<Pubkey>,
+        strategy: AccountSelection,
+        payers: usize,
+        rng: StdRng,
+    ) -> Self {
+        let weighted = match strategy {
+            AccountSelection::Zipf { skew } => accounts
+                .iter()
+                .enumerate()
+                .map(|(rank, &pk)| (pk, 1.0 / (rank as f64 + 1.0).powf(skew)))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let partition_size = (accounts.len() / payers.max(1)).max(1);
+        Self {
+            accounts,
+            strategy,
+            weighted,
+            cursor: 0,
+            partition_size,
+            partition: 0,
+            rng,
+        }
+    }
+
+    /// Sets the index of the payer of the next request, which determines its partition.
+    pub fn set_payer(&mut self, payer: usize) {
+        let partitions = (self.accounts.len() / self.partition_size).max(1);
+        self.partition = payer % partitions;
+    }
+
+    /// # Select Accounts
+    ///
+    /// Selects up to `count` distinct accounts for the next request.
+    pub fn select(&mut self, count: usize) -> Vec<Pubkey> {
+        let count = count.min(self.accounts.len());
+        if count == 0 {
+            return Vec::new();
+        }
+        match self.strategy {
+            AccountSelection::Uniform => self
+                .accounts
+                .choose_multiple(&mut self.rng, count)
+                .copied()
+                .collect(),
+            AccountSelection::Zipf { .. } => self
+                .weighted
+                .choose_multiple_weighted(&mut self.rng, count, |&(_, weight)| weight)
+                .expect("zipf weights should be positive")
+                .map(|&(pk, _)| pk)
+                .collect(),
+            AccountSelection::Sequential => {
+                let len = self.accounts.len();
+                let window = (0..count)
+                    .map(|i| self.accounts[(self.cursor + i) % len])
+                    .collect();
+                self.cursor = (self.cursor + 1) % len;
+                window
+            }
+            AccountSelection::Partitioned => self.accounts[self.partition_range()]
+                .choose_multiple(&mut self.rng, count)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// # Select Available Accounts
+    ///
+    /// Same as `select`, but only draws from the accounts for which `available` holds,
+    /// so that fewer than `count` accounts are selected only if too few are available.
+    pub fn select_available(
+        &mut self,
+        count: usize,
+        available: impl Fn(&Pubkey) -> bool,
+    ) -> Vec<Pubkey> {
+        let accounts: Vec<Pubkey> = match self.strategy {
+            AccountSelection::Uniform => self.accounts.clone(),
+            AccountSelection::Zipf { .. } => {
+                let weighted: Vec<_> = self
+                    .weighted
+                    .iter()
+                    .filter(|(pk, _)| available(pk))
+                    .collect();
+                return weighted
+                    .choose_multiple_weighted(&mut self.rng, count.min(weighted.len()), |w| w.1)
+                    .expect("zipf weights should be positive")
+                    .map(|&&(pk, _)| pk)
+                    .collect();
+            }
+            AccountSelection::Sequential => {
+                // The window skips over the unavailable accounts
+                let len = self.accounts.len();
+                let window = (0..len)
+                    .map(|i| self.accounts[(self.cursor + i) % len])
+                    .filter(|pk| available(pk))
+                    .take(count)
+                    .collect();
+                self.cursor = (self.cursor + 1) % len.max(1);
+                return window;
+            }
+            AccountSelection::Partitioned => self.accounts[self.partition_range()].to_vec(),
+        };
+        let accounts: Vec<_> = accounts.into_iter().filter(|pk| available(pk)).collect();
+        accounts
+            .choose_multiple(&mut self.rng, count)
+            .copied()
+            .collect()
+    }
+
+    /// Returns the range of the partition of the current payer within the accounts.
+    fn partition_range(&self) -> Range<usize> {
+        let start = self.partition * self.partition_size;
+        let end = (start + self.partition_size).min(self.accounts.len());
+        start..end
+    }
+
+    /// Returns all the accounts, which the selector draws from.
+    pub fn accounts(&self) -> &[Pubkey] {
+        &self.accounts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn selector(strategy: AccountSelection, payers: usize) -> AccountSelector {
+        let accounts = (0..8).map(|_| Pubkey::new_unique()).collect();
+        AccountSelector::new(accounts, strategy, payers, StdRng::seed_from_u64(42))
+    }
+
+    #[test]
+    fn partitions_are_disjoint_per_payer() {
+        let mut selector = selector(AccountSelection::Partitioned, 4);
+        let accounts = selector.accounts().to_vec();
+        for payer in 0..4 {
+            selector.set_payer(payer);
+            let mut selected = selector.select(8);
+            selected.sort();
+            let mut partition = accounts[payer * 2..payer * 2 + 2].to_vec();
+            partition.sort();
+            assert_eq!(selected, partition);
+        }
+        // The payers beyond the number of partitions wrap around
+        selector.set_payer(5);
+        assert!(selector
+            .select(2)
+            .iter()
+            .all(|pk| accounts[2..4].contains(pk)));
+    }
+
+    #[test]
+    fn partitions_hold_an_account_at_least() {
+        let mut selector = selector(AccountSelection::Partitioned, 16);
+        let accounts = selector.accounts().to_vec();
+        selector.set_payer(11);
+        assert_eq!(selector.select(4), vec![accounts[3]]);
+    }
+
+    #[test]
+    fn available_accounts_are_selected_within_the_partition() {
+        let mut selector = selector(AccountSelection::Partitioned, 2);
+        let accounts = selector.accounts().to_vec();
+        selector.set_payer(1);
+        let taken = &accounts[4..6];
+        let selected = selector.select_available(4, |pk| !taken.contains(pk));
+        assert_eq!(selected.len(), 2);
+        assert!(selected.iter().all(|pk| accounts[6..8].contains(pk)));
+    }
+
+    #[test]
+    fn zipf_favors_the_top_ranks() {
+        let mut selector = selector(AccountSelection::Zipf { skew: 1.2 }, 1);
+        let accounts = selector.accounts().to_vec();
+        let mut counts = [0; 8];
+        for _ in 0..10_000 {
+            let selected = selector.select(1);
+            let rank = accounts.iter().position(|pk| *pk == selected[0]).unwrap();
+            counts[rank] += 1;
+        }
+        assert!(
+            counts[0] > counts[1] && counts[1] > counts[3] && counts[3] > counts[7],
+            "{counts:?}"
+        );
+        // With a skew of 1.2, the top rank takes more than a third of the draws
+        assert!(counts[0] > 3_333, "{counts:?}");
+    }
+
+    #[test]
+    fn zipf_selects_distinct_accounts() {
+        let mut selector = selector(AccountSelection::Zipf { skew: 2.0 }, 1);
+        let mut selected = selector.select(8);
+        selected.sort();
+        selected.dedup();
+        assert_eq!(selected.len(), 8);
+    }
+
+    #[test]
+    fn sequential_window_slides() {
+        let mut selector = selector(AccountSelection::Sequential, 1);
+        let accounts = selector.accounts().to_vec();
+        assert_eq!(selector.select(3), accounts[0..3]);
+        assert_eq!(selector.select(3), accounts[1..4]);
+        let skipped = accounts[3];
+        assert_eq!(
+            selector.select_available(3, |pk| *pk != skipped),
+            vec![accounts[2], accounts[4], accounts[5]]
+        );
+    }
+}
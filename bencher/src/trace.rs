@@ -0,0 +1,495 @@
+//! Record and replay of request streams.
+//!
+//! A benchmark run can record every request it generates to a trace: a compact
+//! bincode file per benchmark thread, holding the accounts of the thread, followed
+//! by the requests along with the offsets of their send times, and their calls in
+//! order. The replay re-sends the requests with the original timing, or scaled by
+//! the configured speed. RPC calls are replayed verbatim, while transactions are
+//! re-signed with a recent blockhash, since the recorded one has most likely
+//! expired by then. The accounts undelegated by the replayed transactions are
+//! re-delegated, as in the original run.
+
+use core::{config::TraceSettings, types::BenchResult};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::{self, File},
+    io::{self, BufReader, BufWriter},
+    path::Path,
+    time::Duration,
+};
+
+use hyper::Request;
+use json::JsonValueTrait;
+use keypair::Keypair;
+use message::VersionedMessage;
+use program::instruction::Instruction;
+use pubkey::Pubkey;
+use serde::{Deserialize, Serialize};
+use signature::Signature;
+use signer::Signer;
+use transaction::versioned::VersionedTransaction;
+
+use crate::{
+    blockhash::BlockHashProvider,
+    extractor::{signature_response_extractor, value_extractor, Reply},
+    payload::{self, SendOptions},
+    redelegation::RedelegationManager,
+    requests::RequestBuilder,
+};
+
+/// The header of the trace file of a benchmark thread.
+#[derive(Serialize, Deserialize)]
+struct TraceHeader {
+    /// The accounts used by the requests of the thread.
+    accounts: Vec<Pubkey>,
+}
+
+/// A request recorded to the trace.
+#[derive(Serialize, Deserialize)]
+struct TraceRecord {
+    /// The offset of the intended send time from the start of the benchmark, in microseconds.
+    offset: u64,
+    /// The name of the benchmark mode of the request.
+    mode: String,
+    /// The calls of the request, more than one if it's a batch request.
+    calls: Vec<TracedCall>,
+}
+
+/// A call of a recorded request.
+#[derive(Serialize, Deserialize)]
+enum TracedCall {
+    /// An RPC call, replayed verbatim. Its ID matches the one of the replay,
+    /// which assigns the IDs in the same order.
+    Rpc(String),
+    /// A transaction along with its priority fee tier, re-signed on replay.
+    Transaction(VersionedMessage, Option<u64>),
+}
+
+/// # Trace Recorder
+///
+/// Records the requests of a benchmark thread to its trace file.
+pub struct TraceRecorder {
+    writer: BufWriter<File>,
+}
+
+impl TraceRecorder {
+    /// # New Trace Recorder
+    ///
+    /// Creates the trace file of the benchmark thread with the given index, starting
+    /// with the accounts used by its requests.
+    pub fn new(
+        settings: &TraceSettings,
+        thread: usize,
+        accounts: Vec<Pubkey>,
+    ) -> BenchResult<Self> {
+        fs::create_dir_all(&settings.path)?;
+        let mut writer = BufWriter::new(File::create(settings.file(thread))?);
+        bincode::serialize_into(&mut writer, &TraceHeader { accounts })?;
+        Ok(Self { writer })
+    }
+
+    /// # Record Request
+    ///
+    /// Appends the last request generated by the builder to the trace, along with the
+    /// offset of its intended send time from the start of the benchmark.
+    pub fn record(
+        &mut self,
+        offset: Duration,
+        builder: &dyn RequestBuilder,
+        body: &str,
+    ) -> BenchResult<()> {
+        let bodies = if builder.batch_size() == 1 {
+            vec![body.to_owned()]
+        } else {
+            json::to_array_iter(body.as_bytes())
+                .map(|call| Ok(call?.as_raw_str().to_owned()))
+                .collect::<BenchResult<_>>()?
+        };
+        // The transactions follow the order of the `sendTransaction` calls
+        let tiers = builder.fee_tiers();
+        let mut transactions = builder
+            .transactions()
+            .into_iter()
+            .enumerate()
+            .map(|(i, tx)| TracedCall::Transaction(tx.message, tiers.get(i).copied().flatten()));
+        let calls = bodies
+            .into_iter()
+            .map(|body| {
+                let method = json::get(&body, ["method"]);
+                if method.as_str() == Some("sendTransaction") {
+                    transactions
+                        .next()
+                        .ok_or("the transaction of a sendTransaction call is missing".into())
+                } else {
+                    Ok(TracedCall::Rpc(body))
+                }
+            })
+            .collect::<BenchResult<_>>()?;
+        let record = TraceRecord {
+            offset: offset.as_micros() as u64,
+            mode: builder.name().to_owned(),
+            calls,
+        };
+        bincode::serialize_into(&mut self.writer, &record)?;
+        Ok(())
+    }
+}
+
+/// A request loaded from the trace, which is due at the given time.
+struct ReplayRequest {
+    due: Duration,
+    name: &'static str,
+    calls: Vec<TracedCall>,
+}
+
+/// # Replay Request Builder
+///
+/// A request builder that re-sends the requests recorded to the trace of a benchmark
+/// thread, following their recorded schedule.
+pub struct ReplayRequestBuilder {
+    requests: VecDeque<ReplayRequest>,
+    accounts: Vec<Pubkey>,
+    signers: HashMap<Pubkey, Keypair>,
+    blockhash_provider: BlockHashProvider,
+    options: SendOptions,
+    name: &'static str,
+    batch: usize,
+    signatures: Vec<Signature>,
+    fee_tiers: Vec<Option<u64>>,
+    payer: Option<Pubkey>,
+    /// The accounts checked out by every call of the last request.
+    undelegated: Vec<Vec<Pubkey>>,
+    redelegation: Option<RedelegationManager>,
+}
+
+impl ReplayRequestBuilder {
+    /// # Load Trace
+    ///
+    /// Loads the trace of the benchmark thread with the given index, whose transactions
+    /// should be paid for by the given signers, scaling the schedule by the replay speed.
+    /// The accounts undelegated by the transactions are handed over to `redelegation`.
+    pub fn load(
+        settings: &TraceSettings,
+        thread: usize,
+        signers: Vec<Keypair>,
+        blockhash_provider: BlockHashProvider,
+        options: SendOptions,
+        redelegation: Option<RedelegationManager>,
+    ) -> BenchResult<Self> {
+        let path = settings.file(thread);
+        let (header, records) = read(&path)?;
+        let signers: HashMap<_, _> = signers.into_iter().map(|k| (k.pubkey(), k)).collect();
+
+        let mut names: HashMap<String, &'static str> = HashMap::new();
+        let mut requests = VecDeque::with_capacity(records.len());
+        for record in records {
+            let known = record.calls.iter().all(|call| match call {
+                TracedCall::Rpc(_) => true,
+                TracedCall::Transaction(message, _) => {
+                    signers.contains_key(&message.static_account_keys()[0])
+                }
+            });
+            if !known {
+                return Err("the trace was recorded with different payers".into());
+            }
+            // The mode names are few, so they are simply leaked for the lifetime of the replay
+            let name = *names
+                .entry(record.mode)
+                .or_insert_with_key(|mode| Box::leak(mode.clone().into_boxed_str()));
+            requests.push_back(ReplayRequest {
+                due: Duration::from_micros(record.offset).div_f64(settings.speed()),
+                name,
+                calls: record.calls,
+            });
+        }
+        tracing::info!(
+            "replaying {} requests from {}",
+            requests.len(),
+            path.display()
+        );
+        Ok(Self {
+            requests,
+            accounts: header.accounts,
+            signers,
+            blockhash_provider,
+            options,
+            name: "",
+            batch: 1,
+            signatures: Vec::new(),
+            fee_tiers: Vec::new(),
+            payer: None,
+            undelegated: Vec::new(),
+            redelegation,
+        })
+    }
+}
+
+impl RequestBuilder for ReplayRequestBuilder {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+    fn build(&mut self, id: u64) -> Request<String> {
+        let request = self
+            .requests
+            .pop_front()
+            .expect("should not build requests past the end of the trace");
+        self.name = request.name;
+        self.batch = request.calls.len();
+        self.signatures.clear();
+        self.fee_tiers.clear();
+        self.payer = None;
+        self.undelegated.clear();
+        let blockhash = self.blockhash_provider.hash();
+        let mut calls = Vec::with_capacity(request.calls.len());
+        for (call, id) in request.calls.into_iter().zip(id..) {
+            let (mut message, tier) = match call {
+                TracedCall::Rpc(body) => {
+                    calls.push(body);
+                    self.undelegated.push(Vec::new());
+                    continue;
+                }
+                TracedCall::Transaction(message, tier) => (message, tier),
+            };
+            message.set_recent_blockhash(blockhash);
+            let payer = message.static_account_keys()[0];
+            let undelegated = match &self.redelegation {
+                Some(redelegation) => {
+                    redelegation.checkout(undelegated(&message, redelegation.accounts()))
+                }
+                None => Vec::new(),
+            };
+            self.undelegated.push(undelegated);
+            let tx = VersionedTransaction::try_new(message, &[&self.signers[&payer]])
+                .expect("transaction should be signed by its payer");
+            self.signatures.push(tx.signatures[0]);
+            self.fee_tiers.push(tier);
+            self.payer = self.payer.or(Some(payer));
+            calls.push(payload::transaction(&tx, &self.options, id));
+        }
+        if calls.len() == 1 {
+            return Request::new(calls.remove(0));
+        }
+        Request::new(payload::batch(&calls))
+    }
+    fn signature(&self) -> Option<Signature> {
+        self.signatures.last().copied()
+    }
+    fn signatures(&self) -> Vec<Signature> {
+        self.signatures.clone()
+    }
+    fn batch_size(&self) -> usize {
+        self.batch
+    }
+    fn payer(&self) -> Option<Pubkey> {
+        self.payer
+    }
+    fn fee_tier(&self) -> Option<u64> {
+        self.fee_tiers.last().copied().flatten()
+    }
+    fn fee_tiers(&self) -> Vec<Option<u64>> {
+        self.fee_tiers.clone()
+    }
+    fn due(&self) -> Option<Duration> {
+        self.requests.front().map(|r| r.due)
+    }
+    fn undelegated(&self) -> Vec<Vec<Pubkey>> {
+        self.undelegated.clone()
+    }
+    fn exhausted(&self) -> bool {
+        self.requests.is_empty()
+    }
+    fn accounts(&self) -> Vec<Pubkey> {
+        self.accounts.clone()
+    }
+    fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply> {
+        if self.signatures.is_empty() {
+            value_extractor
+        } else {
+            signature_response_extractor
+        }
+    }
+}
+
+/// Returns the accounts undelegated by the `CommitAndUndelegateAccounts` instructions of
+/// the message. The accounts referenced through the lookup table are resolved with
+/// `table`, which holds the addresses of the lookup table of the base payer.
+fn undelegated(message: &VersionedMessage, table: &[Pubkey]) -> Vec<Pubkey> {
+    // The loaded addresses follow the static ones, the writable before the read-only
+    let lookups = message.address_table_lookups().unwrap_or_default();
+    let writable = lookups.iter().flat_map(|l| &l.writable_indexes);
+    let readonly = lookups.iter().flat_map(|l| &l.readonly_indexes);
+    let loaded = writable
+        .chain(readonly)
+        .map(|&i| table.get(i as usize).copied());
+    let keys: Vec<Option<Pubkey>> = message
+        .static_account_keys()
+        .iter()
+        .copied()
+        .map(Some)
+        .chain(loaded)
+        .collect();
+    message
+        .instructions()
+        .iter()
+        .filter(|ix| keys.get(ix.program_id_index as usize) == Some(&Some(program::ID)))
+        .filter(|ix| {
+            let ix = bincode::deserialize(&ix.data);
+            matches!(ix, Ok(Instruction::CommitAndUndelegateAccounts { .. }))
+        })
+        // The payer, the magic context and the magic program precede the accounts
+        .flat_map(|ix| ix.accounts.iter().skip(3))
+        .filter_map(|&i| keys.get(i as usize).copied().flatten())
+        .collect()
+}
+
+/// Reads the header and the records of the trace file at the given path.
+fn read(path: &Path) -> BenchResult<(TraceHeader, Vec<TraceRecord>)> {
+    let file = File::open(path)
+        .map_err(|error| format!("failed to open the trace at {}: {error}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let header = bincode::deserialize_from(&mut reader)?;
+    let mut records = Vec::new();
+    loop {
+        match bincode::deserialize_from(&mut reader) {
+            Ok(record) => records.push(record),
+            // The end of the trace, possibly cut short by an interrupted recording
+            Err(error) if is_eof(&error) => break,
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok((header, records))
+}
+
+/// Tells whether the error is caused by reaching the end of the trace file.
+fn is_eof(error: &bincode::Error) -> bool {
+    matches!(&**error, bincode::ErrorKind::Io(e) if e.kind() == io::ErrorKind::UnexpectedEof)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash::Hash;
+    use std::fs::OpenOptions;
+
+    /// A builder which hands the given calls over to the recorder.
+    struct RecordedRequest {
+        transactions: Vec<VersionedTransaction>,
+        fee_tiers: Vec<Option<u64>>,
+        batch: usize,
+    }
+
+    impl RequestBuilder for RecordedRequest {
+        fn name(&self) -> &'static str {
+            "Mixed"
+        }
+        // The recorder is given the body of the request, along with the builder
+        fn build(&mut self, _id: u64) -> Request<String> {
+            Request::new(String::new())
+        }
+        fn batch_size(&self) -> usize {
+            self.batch
+        }
+        fn fee_tiers(&self) -> Vec<Option<u64>> {
+            self.fee_tiers.clone()
+        }
+        fn transactions(&self) -> Vec<VersionedTransaction> {
+            self.transactions.clone()
+        }
+        fn extractor(&self) -> fn(json::LazyValue) -> Option<Reply> {
+            value_extractor
+        }
+    }
+
+    fn settings(name: &str) -> TraceSettings {
+        TraceSettings {
+            path: std::env::temp_dir().join(format!("redline-{name}-{}", std::process::id())),
+            record: true,
+            speed: None,
+            replay: false,
+        }
+    }
+
+    fn call(id: u64, method: &str) -> String {
+        format!(r#"{{"id":{id},"jsonrpc":"2.0","method":"{method}","params":[]}}"#)
+    }
+
+    /// Records a batch of a transaction between two RPC calls, followed by a single RPC call.
+    fn record(settings: &TraceSettings, accounts: Vec<Pubkey>) -> VersionedTransaction {
+        let payer = Keypair::new();
+        let tx = systransaction::transfer(&payer, &accounts[0], 1, Hash::default()).into();
+        let mut recorder = TraceRecorder::new(settings, 0, accounts).unwrap();
+        let batch = RecordedRequest {
+            transactions: vec![tx],
+            fee_tiers: vec![Some(7)],
+            batch: 3,
+        };
+        let calls = [
+            call(1, "getBalance"),
+            call(2, "sendTransaction"),
+            call(3, "getAccountInfo"),
+        ];
+        let body = payload::batch(&calls);
+        recorder
+            .record(Duration::from_millis(5), &batch, &body)
+            .unwrap();
+        let single = RecordedRequest {
+            transactions: Vec::new(),
+            fee_tiers: Vec::new(),
+            batch: 1,
+        };
+        let body = call(4, "getBalance");
+        recorder
+            .record(Duration::from_millis(9), &single, &body)
+            .unwrap();
+        batch.transactions[0].clone()
+    }
+
+    #[test]
+    fn trace_round_trip() {
+        let settings = settings("round-trip");
+        let accounts = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        let tx = record(&settings, accounts.clone());
+
+        let (header, records) = read(&settings.file(0)).unwrap();
+        assert_eq!(header.accounts, accounts);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].offset, 5_000);
+        assert_eq!(records[0].mode, "Mixed");
+        // The calls of the batch keep their positions
+        let [first, second, third] = &records[0].calls[..] else {
+            panic!("the batch should hold 3 calls");
+        };
+        assert!(matches!(first, TracedCall::Rpc(body) if *body == call(1, "getBalance")));
+        let TracedCall::Transaction(message, tier) = second else {
+            panic!("the second call should be the transaction");
+        };
+        assert_eq!(*message, tx.message);
+        assert_eq!(*tier, Some(7));
+        assert!(matches!(third, TracedCall::Rpc(body) if *body == call(3, "getAccountInfo")));
+        assert_eq!(records[1].offset, 9_000);
+        let [single] = &records[1].calls[..] else {
+            panic!("the single request should hold 1 call");
+        };
+        assert!(matches!(single, TracedCall::Rpc(body) if *body == call(4, "getBalance")));
+        fs::remove_dir_all(&settings.path).unwrap();
+    }
+
+    #[test]
+    fn truncated_trace_ends_at_the_last_whole_record() {
+        let settings = settings("truncated");
+        record(&settings, vec![Pubkey::new_unique()]);
+
+        // An interrupted recording leaves a partially written record behind
+        let file = OpenOptions::new()
+            .write(true)
+            .open(settings.file(0))
+            .unwrap();
+        let len = file.metadata().unwrap().len();
+        file.set_len(len - 3).unwrap();
+
+        let (_, records) = read(&settings.file(0)).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].calls.len(), 3);
+        fs::remove_dir_all(&settings.path).unwrap();
+    }
+}
@@ -1,13 +1,19 @@
-use core::types::BenchMode;
+use core::{
+    config::ModeOptions,
+    types::{AccountSelection, AccountSharing, BenchMode, PriorityFee},
+};
 use hash::Hash;
 use instruction::{AccountMeta, Instruction as SolanaInstruction};
 use keypair::Keypair;
+use message::{v0, AddressLookupTableAccount, VersionedMessage};
 use program::instruction::Instruction;
 use pubkey::Pubkey;
-use rand::{rngs::ThreadRng, seq::SliceRandom, thread_rng};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use sdk::consts::{MAGIC_CONTEXT_ID, MAGIC_PROGRAM_ID};
 use signer::Signer;
-use transaction::Transaction;
+use transaction::{versioned::VersionedTransaction, Transaction};
+
+use crate::{redelegation::RedelegationManager, selector::AccountSelector};
 
 /// # Transaction Provider Trait
 ///
@@ -16,48 +22,150 @@ use transaction::Transaction;
 pub trait TransactionProvider {
     /// Returns the name of the benchmark mode.
     fn name(&self) -> &'static str;
-    /// Generates the instruction for the transaction.
-    fn generate_ix(&mut self, id: u64) -> SolanaInstruction;
+    /// Generates the instructions for the transaction, a single one unless it's composite.
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction>;
 
     /// Wraps a given instruction in a `SolanaInstruction`.
     fn wrap_ix(&self, ix: Instruction, accounts: Vec<AccountMeta>) -> SolanaInstruction {
         SolanaInstruction::new_with_bincode(program::ID, &ix, accounts)
     }
 
-    /// Generates a complete, signed transaction.
-    fn generate(&mut self, id: u64, blockhash: Hash, signer: &Keypair) -> Transaction {
-        let ix = self.generate_ix(id);
-        let mut tx = Transaction::new_with_payer(&[ix], Some(&signer.pubkey()));
-        tx.sign(&[signer], blockhash);
-        tx
+    /// Generates a complete, signed transaction, prepending the given compute budget instructions.
+    /// With lookup tables, a v0 transaction referencing their addresses is generated instead of
+    /// a legacy one.
+    fn generate(
+        &mut self,
+        id: u64,
+        blockhash: Hash,
+        signer: &Keypair,
+        budget: &[SolanaInstruction],
+        tables: &[AddressLookupTableAccount],
+    ) -> VersionedTransaction {
+        let mut ixs = budget.to_vec();
+        ixs.extend(self.generate_ixs(id));
+        let payer = signer.pubkey();
+        if tables.is_empty() {
+            let mut tx = Transaction::new_with_payer(&ixs, Some(&payer));
+            tx.sign(&[signer], blockhash);
+            return tx.into();
+        }
+        let message = v0::Message::try_compile(&payer, &ixs, tables, blockhash)
+            .expect("transaction should compile with the lookup tables");
+        VersionedTransaction::try_new(VersionedMessage::V0(message), &[signer])
+            .expect("transaction should be signed by its payer")
+    }
+
+    /// Sets the index of the payer of the next transaction, which determines
+    /// the partition of its accounts, if they are partitioned per payer.
+    fn set_payer(&mut self, payer: usize);
+
+    /// Returns whether the last transaction has no accounts to operate on, since all of
+    /// them were taken out of the pool, so it shouldn't be sent.
+    fn starved(&self) -> bool {
+        false
+    }
+
+    /// Returns the accounts checked out for undelegation by the last transaction.
+    fn undelegated(&self) -> Vec<Pubkey> {
+        Vec::new()
     }
 
     /// Returns a list of accounts used by the transaction provider.
     fn accounts(&self) -> Vec<Pubkey>;
 }
 
+/// The ID of the compute budget program.
+const COMPUTE_BUDGET_ID: Pubkey =
+    Pubkey::from_str_const("ComputeBudget111111111111111111111111111111");
+
+/// The maximum size of a serialized transaction, which has to fit into a single packet.
+pub const MAX_TRANSACTION_SIZE: u64 = 1232;
+
+/// # Compute Budget
+///
+/// Generates the compute budget instructions to prepend to the transactions of a mode,
+/// drawing the priority fee of every transaction from the configured distribution.
+pub struct ComputeBudget {
+    /// The compute unit limit, set with `SetComputeUnitLimit`.
+    unit_limit: Option<u32>,
+    /// The distribution of compute unit prices, set with `SetComputeUnitPrice`.
+    priority_fee: Option<PriorityFee>,
+    /// The heap frame size, requested with `RequestHeapFrame`.
+    heap_frame: Option<u32>,
+    /// The priority fee tier of the last transaction, if the payers are partitioned into tiers.
+    tier: Option<u64>,
+    rng: StdRng,
+}
+
+impl ComputeBudget {
+    /// Creates the compute budget from the options of a benchmark mode, drawing the
+    /// priority fees with `rng`.
+    pub fn new(options: &ModeOptions, rng: StdRng) -> Self {
+        Self {
+            unit_limit: options.compute_unit_limit,
+            priority_fee: options.priority_fee.clone(),
+            heap_frame: options.heap_frame_bytes,
+            tier: None,
+            rng,
+        }
+    }
+
+    /// Returns the compute budget instructions for a transaction from the payer with the
+    /// given index, which is empty if none of the options are configured.
+    pub fn instructions(&mut self, payer: usize) -> Vec<SolanaInstruction> {
+        // The instruction data is the borsh-encoded `ComputeBudgetInstruction`
+        let ix = |tag: u8, value: &[u8]| {
+            let data = [&[tag], value].concat();
+            SolanaInstruction::new_with_bytes(COMPUTE_BUDGET_ID, &data, Vec::new())
+        };
+        let mut ixs = Vec::new();
+        if let Some(bytes) = self.heap_frame {
+            ixs.push(ix(1, &bytes.to_le_bytes()));
+        }
+        if let Some(units) = self.unit_limit {
+            ixs.push(ix(2, &units.to_le_bytes()));
+        }
+        self.tier = None;
+        let price = match &self.priority_fee {
+            Some(PriorityFee::Fixed(price)) => Some(*price),
+            Some(PriorityFee::Uniform { min, max }) => Some(self.rng.gen_range(*min..=*max)),
+            Some(PriorityFee::Tiers(tiers)) if !tiers.is_empty() => {
+                self.tier = Some(tiers[payer % tiers.len()]);
+                self.tier
+            }
+            _ => None,
+        };
+        if let Some(price) = price {
+            ixs.push(ix(3, &price.to_le_bytes()));
+        }
+        ixs
+    }
+
+    /// Returns the priority fee tier of the last transaction, if the payers are
+    /// partitioned into tiers.
+    pub fn tier(&self) -> Option<u64> {
+        self.tier
+    }
+}
+
 /// # Base Provider
 ///
 /// Generic provider for simple transaction patterns with consistent account selection.
 struct BaseProvider<const READONLY: bool = false> {
-    accounts: Vec<Pubkey>,
+    selector: AccountSelector,
     count: usize,
-    rng: ThreadRng,
 }
 
 impl<const RO: bool> BaseProvider<RO> {
-    fn new(accounts: Vec<Pubkey>, count: usize) -> Self {
-        Self {
-            accounts,
-            count,
-            rng: thread_rng(),
-        }
+    fn new(selector: AccountSelector, count: usize) -> Self {
+        Self { selector, count }
     }
 
     fn random_accounts(&mut self) -> Vec<AccountMeta> {
-        self.accounts
-            .choose_multiple(&mut self.rng, self.count)
-            .map(|&pk| {
+        self.selector
+            .select(self.count)
+            .into_iter()
+            .map(|pk| {
                 if RO {
                     AccountMeta::new_readonly(pk, false)
                 } else {
@@ -79,10 +187,9 @@ pub struct SimpleByteSetProvider(BaseProvider<false>);
 /// Generates transactions with a high computational cost to stress the validator's
 /// processing capabilities, writing results to multiple accounts.
 pub struct HighCuCostProvider {
-    accounts: Vec<Pubkey>,
+    selector: AccountSelector,
     iters: u32,
     accounts_per_transaction: usize,
-    rng: ThreadRng,
 }
 
 /// # ReadWrite Provider
@@ -90,9 +197,8 @@ pub struct HighCuCostProvider {
 /// Generates transactions that perform read and write operations across multiple accounts,
 /// which is useful for testing lock contention.
 pub struct ReadWriteProvider {
-    accounts: Vec<Pubkey>,
+    selector: AccountSelector,
     accounts_per_transaction: usize,
-    rng: ThreadRng,
 }
 
 /// # ReadOnly Provider
@@ -105,17 +211,44 @@ pub struct ReadOnlyProvider(BaseProvider<true>);
 /// Generates transactions that commit the state to the base chain in the Ephemeral Rollup.
 pub struct CommitProvider(BaseProvider<true>);
 
+/// # CommitAndUndelegate Provider
+///
+/// Generates transactions that commit the accounts to the base chain and undelegate them
+/// from the Ephemeral Rollup. The accounts still being re-delegated are skipped.
+pub struct CommitAndUndelegateProvider {
+    selector: AccountSelector,
+    accounts_per_transaction: usize,
+    payers: Vec<Pubkey>,
+    payer: usize,
+    redelegation: RedelegationManager,
+    /// The accounts checked out by the last transaction, which is starved if there are none.
+    undelegated: Vec<Pubkey>,
+}
+
+/// # Composite Provider
+///
+/// Generates transactions which pack the instructions of several other providers,
+/// one provider for every instruction in the transaction.
+pub struct CompositeProvider {
+    providers: Vec<Box<dyn TransactionProvider>>,
+    accounts: Vec<Pubkey>,
+}
+
 impl TransactionProvider for SimpleByteSetProvider {
     fn name(&self) -> &'static str {
         "SimpleByteSet"
     }
-    fn generate_ix(&mut self, id: u64) -> SolanaInstruction {
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction> {
         let ix = Instruction::SimpleByteSet { id };
         let accounts = self.0.random_accounts();
-        self.wrap_ix(ix, accounts)
+        vec![self.wrap_ix(ix, accounts)]
     }
+    fn set_payer(&mut self, payer: usize) {
+        self.0.selector.set_payer(payer);
+    }
+
     fn accounts(&self) -> Vec<Pubkey> {
-        self.0.accounts.clone()
+        self.0.selector.accounts().to_vec()
     }
 }
 
@@ -123,25 +256,29 @@ impl TransactionProvider for HighCuCostProvider {
     fn name(&self) -> &'static str {
         "HighCuCost"
     }
-    fn generate_ix(&mut self, id: u64) -> SolanaInstruction {
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction> {
         // Use first account as init parameter
-        let init = self.accounts[0];
+        let init = self.selector.accounts()[0];
         let ix = Instruction::ExpensiveHashCompute {
             id,
             init,
             iters: self.iters,
         };
-        // Randomly select accounts for this transaction
-        let selected = self
-            .accounts
-            .choose_multiple(&mut self.rng, self.accounts_per_transaction)
-            .copied();
-        let accounts = selected.map(|pda| AccountMeta::new(pda, false)).collect();
-        self.wrap_ix(ix, accounts)
+        // Select the accounts for this transaction
+        let selected = self.selector.select(self.accounts_per_transaction);
+        let accounts = selected
+            .into_iter()
+            .map(|pda| AccountMeta::new(pda, false))
+            .collect();
+        vec![self.wrap_ix(ix, accounts)]
+    }
+
+    fn set_payer(&mut self, payer: usize) {
+        self.selector.set_payer(payer);
     }
 
     fn accounts(&self) -> Vec<Pubkey> {
-        self.accounts.clone()
+        self.selector.accounts().to_vec()
     }
 }
 
@@ -149,16 +286,12 @@ impl TransactionProvider for ReadWriteProvider {
     fn name(&self) -> &'static str {
         "ReadWrite"
     }
-    fn generate_ix(&mut self, id: u64) -> SolanaInstruction {
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction> {
         let ix = Instruction::AccountDataCopy { id };
-        // Randomly select accounts for this transaction
-        let selected = self
-            .accounts
-            .choose_multiple(&mut self.rng, self.accounts_per_transaction)
-            .copied();
+        // Select the accounts for this transaction
+        let all = self.selector.select(self.accounts_per_transaction);
 
         // First half read-only, second half writable (50/50 split)
-        let all: Vec<_> = selected.collect();
         let split = all.len() / 2;
         let split = if split == 0 { 1 } else { split };
 
@@ -170,11 +303,15 @@ impl TransactionProvider for ReadWriteProvider {
             accounts.push(AccountMeta::new(pk, false));
         }
 
-        self.wrap_ix(ix, accounts)
+        vec![self.wrap_ix(ix, accounts)]
+    }
+
+    fn set_payer(&mut self, payer: usize) {
+        self.selector.set_payer(payer);
     }
 
     fn accounts(&self) -> Vec<Pubkey> {
-        self.accounts.clone()
+        self.selector.accounts().to_vec()
     }
 }
 
@@ -182,14 +319,18 @@ impl TransactionProvider for ReadOnlyProvider {
     fn name(&self) -> &'static str {
         "ReadOnly"
     }
-    fn generate_ix(&mut self, id: u64) -> SolanaInstruction {
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction> {
         let ix = Instruction::ReadAccountsData { id };
         let accounts = self.0.random_accounts();
-        self.wrap_ix(ix, accounts)
+        vec![self.wrap_ix(ix, accounts)]
+    }
+
+    fn set_payer(&mut self, payer: usize) {
+        self.0.selector.set_payer(payer);
     }
 
     fn accounts(&self) -> Vec<Pubkey> {
-        self.0.accounts.clone()
+        self.0.selector.accounts().to_vec()
     }
 }
 
@@ -197,60 +338,205 @@ impl TransactionProvider for CommitProvider {
     fn name(&self) -> &'static str {
         "Commit"
     }
-    fn generate_ix(&mut self, id: u64) -> SolanaInstruction {
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction> {
         let ix = Instruction::CommitAccounts { id };
         let mut accounts = vec![
             AccountMeta::new(MAGIC_CONTEXT_ID, false),
             AccountMeta::new_readonly(MAGIC_PROGRAM_ID, false),
         ];
         accounts.extend(self.0.random_accounts());
-        self.wrap_ix(ix, accounts)
+        vec![self.wrap_ix(ix, accounts)]
+    }
+
+    fn set_payer(&mut self, payer: usize) {
+        self.0.selector.set_payer(payer);
+    }
+
+    fn accounts(&self) -> Vec<Pubkey> {
+        self.0.selector.accounts().to_vec()
+    }
+}
+
+impl TransactionProvider for CommitAndUndelegateProvider {
+    fn name(&self) -> &'static str {
+        "CommitAndUndelegate"
+    }
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction> {
+        let ix = Instruction::CommitAndUndelegateAccounts { id };
+        let mut accounts = vec![
+            AccountMeta::new(self.payers[self.payer], true),
+            AccountMeta::new(MAGIC_CONTEXT_ID, false),
+            AccountMeta::new_readonly(MAGIC_PROGRAM_ID, false),
+        ];
+        // The selected accounts are undelegated, until the manager delegates them again,
+        // so only the accounts which aren't in the cycle are selected
+        let redelegation = &self.redelegation;
+        let selected = self
+            .selector
+            .select_available(self.accounts_per_transaction, |pk| {
+                !redelegation.cycling(pk)
+            });
+        self.undelegated = redelegation.checkout(selected);
+        accounts.extend(
+            self.undelegated
+                .iter()
+                .map(|&pk| AccountMeta::new(pk, false)),
+        );
+        vec![self.wrap_ix(ix, accounts)]
+    }
+
+    fn set_payer(&mut self, payer: usize) {
+        self.payer = payer;
+        self.selector.set_payer(payer);
+    }
+
+    fn starved(&self) -> bool {
+        self.undelegated.is_empty()
+    }
+
+    fn undelegated(&self) -> Vec<Pubkey> {
+        self.undelegated.clone()
     }
 
     fn accounts(&self) -> Vec<Pubkey> {
-        self.0.accounts.clone()
+        self.selector.accounts().to_vec()
+    }
+}
+
+impl TransactionProvider for CompositeProvider {
+    fn name(&self) -> &'static str {
+        "Composite"
+    }
+    fn generate_ixs(&mut self, id: u64) -> Vec<SolanaInstruction> {
+        self.providers
+            .iter_mut()
+            .flat_map(|p| p.generate_ixs(id))
+            .collect()
+    }
+    fn set_payer(&mut self, payer: usize) {
+        for provider in &mut self.providers {
+            provider.set_payer(payer);
+        }
+    }
+
+    fn starved(&self) -> bool {
+        self.providers.iter().any(|p| p.starved())
+    }
+
+    fn undelegated(&self) -> Vec<Pubkey> {
+        self.providers
+            .iter()
+            .flat_map(|p| p.undelegated())
+            .collect()
+    }
+
+    fn accounts(&self) -> Vec<Pubkey> {
+        self.accounts.clone()
     }
 }
 
 /// # Make Provider
 ///
-/// A factory function that creates a transaction provider based on the provided benchmark mode.
-pub fn make_provider(mode: &BenchMode, accounts: Vec<Pubkey>) -> Box<dyn TransactionProvider> {
+/// A factory function that creates a transaction provider based on the provided benchmark mode,
+/// selecting the accounts of its transactions with the given strategy. The random choices of
+/// the provider are derived from `rng`. The modes which undelegate accounts hand them over
+/// to the re-delegation manager.
+pub fn make_provider(
+    mode: &BenchMode,
+    accounts: Vec<Pubkey>,
+    selection: AccountSelection,
+    payers: &[Pubkey],
+    redelegation: Option<&RedelegationManager>,
+    rng: &mut StdRng,
+) -> Box<dyn TransactionProvider> {
+    let selector = |rng: &mut StdRng| {
+        let rng = StdRng::seed_from_u64(rng.gen());
+        AccountSelector::new(accounts.clone(), selection, payers.len(), rng)
+    };
     match mode {
         BenchMode::SimpleByteSet {
             accounts_per_transaction,
         } => Box::new(SimpleByteSetProvider(BaseProvider::new(
-            accounts,
+            selector(rng),
             *accounts_per_transaction as usize,
         ))),
         BenchMode::ReadWrite {
             accounts_per_transaction,
         } => Box::new(ReadWriteProvider {
-            accounts,
+            selector: selector(rng),
             accounts_per_transaction: *accounts_per_transaction as usize,
-            rng: thread_rng(),
         }),
         BenchMode::HighCuCost {
             iters,
             accounts_per_transaction,
         } => Box::new(HighCuCostProvider {
-            accounts,
+            selector: selector(rng),
             iters: *iters,
             accounts_per_transaction: *accounts_per_transaction as usize,
-            rng: thread_rng(),
         }),
         BenchMode::ReadOnly {
             accounts_per_transaction,
         } => Box::new(ReadOnlyProvider(BaseProvider::new(
-            accounts,
+            selector(rng),
             *accounts_per_transaction as usize,
         ))),
         BenchMode::Commit {
             accounts_per_transaction,
         } => Box::new(CommitProvider(BaseProvider::new(
-            accounts,
+            selector(rng),
             *accounts_per_transaction as usize,
         ))),
+        BenchMode::CommitAndUndelegate {
+            accounts_per_transaction,
+        } => Box::new(CommitAndUndelegateProvider {
+            selector: selector(rng),
+            accounts_per_transaction: *accounts_per_transaction as usize,
+            payers: payers.to_vec(),
+            payer: 0,
+            redelegation: redelegation
+                .expect("undelegating modes should have a re-delegation manager")
+                .clone(),
+            undelegated: Vec::new(),
+        }),
+        BenchMode::Composite {
+            instructions,
+            account_sharing,
+        } => {
+            let slots: Vec<&BenchMode> = instructions
+                .iter()
+                .flat_map(|i| std::iter::repeat_n(&i.mode, i.count as usize))
+                .collect();
+            let providers = match account_sharing {
+                AccountSharing::Shared => slots
+                    .into_iter()
+                    .map(|mode| {
+                        make_provider(mode, accounts.clone(), selection, payers, redelegation, rng)
+                    })
+                    .collect(),
+                // Validated to have at least one account per instruction
+                AccountSharing::Disjoint => {
+                    let size = accounts.len() / slots.len();
+                    slots
+                        .into_iter()
+                        .zip(accounts.chunks(size))
+                        .map(|(mode, partition)| {
+                            make_provider(
+                                mode,
+                                partition.to_vec(),
+                                selection,
+                                payers,
+                                redelegation,
+                                rng,
+                            )
+                        })
+                        .collect()
+                }
+            };
+            Box::new(CompositeProvider {
+                providers,
+                accounts,
+            })
+        }
         // This function is only for transaction-based modes, so it will panic
         // if an RPC-based mode is provided.
         _ => panic!("Unsupported mode for make_provider"),
@@ -1,4 +1,4 @@
-use core::{config::Config, types::ConnectionType};
+use core::config::Config;
 use std::{
     collections::VecDeque,
     time::{Duration, Instant},
@@ -9,8 +9,10 @@ use keypair::Keypair;
 use pubkey::Pubkey;
 
 use crate::{
-    blockhash::BlockHashProvider, extractor::signature_response_extractor, http::Connection,
-    payload,
+    blockhash::BlockHashProvider,
+    extractor::signature_extractor,
+    http::Connection,
+    payload::{self, SendOptions},
 };
 
 /// # Transfer Manager
@@ -33,6 +35,8 @@ pub struct TransferManager {
     frequency: Duration,
     /// A provider for fetching and caching the latest blockhash.
     blockhash: BlockHashProvider,
+    /// The options of the transfer transactions, which skip the preflight check.
+    options: SendOptions,
 }
 
 impl TransferManager {
@@ -49,7 +53,7 @@ impl TransferManager {
         // The frequency of the transfers is configured in the benchmark settings.
         let frequency = Duration::from_millis(config.benchmark.clone_frequency_ms);
         let last = Instant::now();
-        let chain = Connection::new(&config.connection.chain_url, ConnectionType::Http2)
+        let chain = Connection::chain(&config.connection)
             .await
             .expect("failed to connect to chain endpoint");
         Self {
@@ -59,6 +63,10 @@ impl TransferManager {
             last,
             frequency,
             blockhash,
+            options: SendOptions {
+                preflight: false,
+                ..SendOptions::new(config)
+            },
         }
     }
 
@@ -85,13 +93,15 @@ impl TransferManager {
         let blockhash = self.blockhash.hash();
         // Create a new system transfer transaction.
         let txn = systransaction::transfer(&self.vault, &pda, 1, blockhash);
-        let request = Request::new(payload::transaction(&txn, false));
+        let request = Request::new(payload::transaction(&txn.into(), &self.options, 1));
 
         // Asynchronously send the transaction and handle the response.
-        let response = self.chain.send(request, signature_response_extractor);
+        let response = self.chain.send(request, signature_extractor);
         tokio::task::spawn_local(async move {
-            if let Err(err) = response.resolve().await {
-                tracing::error!(%err, "failed to airdrop to pda (clone trigger)");
+            match response.resolve().await {
+                Ok(Some(signature)) => tracing::debug!(%signature, %pda, "airdropped to pda"),
+                Ok(None) => tracing::warn!(%pda, "got no signature for the airdrop to pda"),
+                Err(err) => tracing::error!(%err, "failed to airdrop to pda (clone trigger)"),
             }
         });
         // Add the PDA back to the end of the queue to be used again later.
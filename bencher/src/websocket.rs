@@ -1,27 +1,45 @@
 //! WebSocket subscription management with graceful shutdown.
 //!
-//! Manages multiple WS connections using round-robin distribution.
+//! Manages multiple WS connections to each endpoint using round-robin distribution.
 //! Handles subscription confirmations and routes notifications to
 //! appropriate channels. Buffers out-of-order messages until subscription confirmed.
+//! Reconnects dropped connections with backoff, re-issuing persistent subscriptions.
 
-use core::{config::ConnectionSettings, types::Url};
-use std::collections::{hash_map::Entry, HashMap};
+use core::{config::ConnectionSettings, stats::WebsocketStats, types::Url};
+use std::{
+    cell::RefCell,
+    collections::{hash_map::Entry, HashMap},
+    rc::Rc,
+    time::Duration,
+};
 
 use fastwebsockets::{handshake, CloseCode, Frame, OpCode, Payload, WebSocket};
 use http_body_util::Empty;
 use hyper::{
-    header::{CONNECTION, UPGRADE},
+    header::{HeaderMap, CONNECTION, UPGRADE},
     upgrade::Upgraded,
     Request,
 };
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use json::{Deserialize, JsonValueTrait, LazyValue};
-use tokio::{
-    net::TcpStream,
-    sync::mpsc::{self, Receiver, Sender},
+use tokio::sync::mpsc::{self, Receiver, Sender};
+use tokio_rustls::TlsConnector;
+
+use crate::{
+    http,
+    tls::{self, ALPN_HTTP1},
+    BenchResult, ShutDown, ShutDownListener,
 };
 
-use crate::{BenchResult, ShutDown, ShutDownListener};
+/// The delay before the first reconnection attempt of a dropped connection.
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// The upper bound on the delay in between the reconnection attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// A WebSocket connection to the RPC server.
+type WsConnection = WebSocket<TokioIo<Upgraded>>;
+/// Shared counters for the health of the WebSocket connections.
+pub type WebsocketStatsDB = Rc<RefCell<WebsocketStats>>;
 
 /// # WebSocket Worker
 ///
@@ -29,12 +47,16 @@ use crate::{BenchResult, ShutDown, ShutDownListener};
 /// and graceful shutdown. It is generic over the extractor function `F` and the
 /// extracted value `V`.
 pub struct WsWorker<F, V> {
-    ws: WebSocket<TokioIo<Upgraded>>,
+    ws: WsConnection,
+    url: Url,
+    tls: Option<TlsConnector>,
+    headers: HeaderMap,
     rx: ShutDownReceiver<Subscription<V>>,
     subscriptions: HashMap<u64, Subscription<V>>,
     pending: HashMap<u64, Subscription<V>>,
     buffered: HashMap<u64, Payload<'static>>,
     extractor: F,
+    stats: WebsocketStatsDB,
 }
 
 /// # Subscription
@@ -51,10 +73,33 @@ pub struct Subscription<V> {
 /// # WebSocket Pool
 ///
 /// Manages a pool of `WsWorker` instances to handle multiple concurrent WebSocket connections,
-/// distributing the load and providing a simple interface for obtaining a connection.
+/// distributing the load and providing a simple interface for obtaining a connection
+/// to each of the endpoints.
 pub struct WebsocketPool<V> {
-    connections: Vec<Sender<Subscription<V>>>,
-    next: usize,
+    connections: Vec<Vec<Sender<Subscription<V>>>>,
+    next: Vec<usize>,
+}
+
+/// Establishes a WebSocket connection to the given URL, over TLS if a connector is provided,
+/// setting the extra headers on the upgrade request.
+async fn connect(
+    url: &Url,
+    tls: Option<&TlsConnector>,
+    headers: &HeaderMap,
+) -> BenchResult<WsConnection> {
+    let stream = tls::connect(url, tls).await?;
+    let mut req = Request::builder()
+        .method("GET")
+        .uri(url.path())
+        .header("Host", url.host())
+        .header(UPGRADE, "websocket")
+        .header(CONNECTION, "upgrade")
+        .header("Sec-WebSocket-Key", handshake::generate_key())
+        .header("Sec-WebSocket-Version", "13")
+        .body(Empty::<&[u8]>::new())?;
+    req.headers_mut().extend(headers.clone());
+    let (ws, _) = handshake::client(&TokioExecutor::new(), req, stream).await?;
+    Ok(ws)
 }
 
 /// Subscription confirmation message from WebSocket server.
@@ -74,30 +119,27 @@ where
     /// Establishes a WebSocket connection and spawns a new `WsWorker` to manage it.
     async fn init(
         url: &Url,
+        tls: Option<TlsConnector>,
+        headers: HeaderMap,
         extractor: F,
         shutdown: ShutDownListener,
+        stats: WebsocketStatsDB,
     ) -> BenchResult<Sender<Subscription<V>>> {
-        let stream = TcpStream::connect(url.address(true)).await?;
-        let req = Request::builder()
-            .method("GET")
-            .uri(&url.0)
-            .header("Host", url.host())
-            .header(UPGRADE, "websocket")
-            .header(CONNECTION, "upgrade")
-            .header("Sec-WebSocket-Key", handshake::generate_key())
-            .header("Sec-WebSocket-Version", "13")
-            .body(Empty::<&[u8]>::new())?;
-        let (ws, _) = handshake::client(&TokioExecutor::new(), req, stream).await?;
+        let ws = connect(url, tls.as_ref(), &headers).await?;
         let (tx, rx) = mpsc::channel(1);
         let rx = ShutDownReceiver { rx, shutdown };
 
         let this = Self {
             ws,
+            url: url.clone(),
+            tls,
+            headers,
             rx,
             subscriptions: HashMap::default(),
             pending: HashMap::default(),
             extractor,
             buffered: HashMap::default(),
+            stats,
         };
 
         tokio::task::spawn_local(this.run());
@@ -152,43 +194,117 @@ where
         }
     }
 
-    /// Handles a new subscription request. Returns false if shutdown was requested.
-    async fn handle_subscription(&mut self, sub: Option<Subscription<V>>) -> bool {
-        let Some(mut sub) = sub else {
-            let _ = self
-                .ws
-                .write_frame(Frame::close(CloseCode::Normal.into(), b""))
-                .await;
-            return false;
+    /// Sends a new subscription request. Returns false if the connection was dropped,
+    /// in which case the subscription is left pending, to be handled on reconnection.
+    async fn handle_subscription(&mut self, mut sub: Subscription<V>) -> bool {
+        // Persistent subscriptions keep their payload, to be re-issued on reconnection
+        let payload = if sub.oneshot {
+            std::mem::take(&mut sub.payload)
+        } else {
+            sub.payload.clone()
         };
-
-        let payload = Payload::Owned(std::mem::take(&mut sub.payload).into_bytes());
-        // TODO: reconnect on error
-        self.ws
-            .write_frame(Frame::text(payload))
-            .await
-            .expect("failed to send data websocket");
-        self.ws.flush().await.expect("failed to flush ws stream");
+        let payload = Payload::Owned(payload.into_bytes());
+        let sent = self.ws.write_frame(Frame::text(payload)).await.is_ok();
+        let sent = sent && self.ws.flush().await.is_ok();
         self.pending.insert(sub.id, sub);
+        sent
+    }
+
+    /// # Reconnect
+    ///
+    /// Re-establishes a dropped connection, retrying with an exponential backoff, and
+    /// re-issues all the persistent subscriptions. Oneshot subscriptions which didn't
+    /// receive their notification are counted as lost. Returns false if shutdown was
+    /// requested in the meantime.
+    async fn reconnect(&mut self) -> bool {
+        let mut backoff = INITIAL_BACKOFF;
+        loop {
+            // Server-side subscription IDs don't survive the connection
+            self.buffered.clear();
+            let mut resubscribe = Vec::new();
+            let dropped = self.subscriptions.drain().chain(self.pending.drain());
+            for (_, sub) in dropped {
+                if sub.oneshot {
+                    self.stats.borrow_mut().lost_subscriptions += 1;
+                } else {
+                    resubscribe.push(sub);
+                }
+            }
+
+            tracing::warn!(url = %self.url.0, "websocket connection dropped, reconnecting");
+            loop {
+                let delay = tokio::time::sleep(backoff);
+                tokio::pin!(delay);
+                // Keep accepting the subscriptions, so that the senders don't stall
+                loop {
+                    tokio::select! {
+                        _ = &mut delay => break,
+                        _ = self.rx.shutdown.recv() => return false,
+                        Some(sub) = self.rx.rx.recv() => {
+                            if sub.oneshot {
+                                self.stats.borrow_mut().lost_subscriptions += 1;
+                            } else {
+                                resubscribe.push(sub);
+                            }
+                        }
+                    }
+                }
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                match connect(&self.url, self.tls.as_ref(), &self.headers).await {
+                    Ok(ws) => {
+                        self.ws = ws;
+                        break;
+                    }
+                    Err(error) => tracing::warn!(%error, ?backoff, "failed to reconnect websocket"),
+                }
+            }
+            self.stats.borrow_mut().reconnects += 1;
 
-        true
+            let mut connected = true;
+            for sub in resubscribe {
+                if connected {
+                    connected = self.handle_subscription(sub).await;
+                } else {
+                    self.pending.insert(sub.id, sub);
+                }
+            }
+            if connected {
+                return true;
+            }
+        }
     }
 
     /// # Run WebSocket Worker
     ///
     /// The main loop for the `WsWorker`, handling incoming messages, subscriptions,
-    /// and shutdown signals.
+    /// and shutdown signals. Dropped connections are re-established.
     async fn run(mut self) {
         loop {
-            tokio::select! {
-                Ok(frame) = self.ws.read_frame() => {
-                    self.handle_frame(frame).await;
-                }
+            let connected = tokio::select! {
+                frame = self.ws.read_frame() => match frame {
+                    Ok(frame) if matches!(frame.opcode, OpCode::Close) => false,
+                    Ok(frame) => {
+                        self.handle_frame(frame).await;
+                        true
+                    }
+                    Err(error) => {
+                        tracing::warn!(%error, "failed to read from websocket");
+                        false
+                    }
+                },
                 sub = self.rx.recv() => {
-                    if !self.handle_subscription(sub).await {
+                    let Some(sub) = sub else {
+                        let _ = self
+                            .ws
+                            .write_frame(Frame::close(CloseCode::Normal.into(), b""))
+                            .await;
                         break;
-                    }
+                    };
+                    self.handle_subscription(sub).await
                 }
+            };
+            if !connected && !self.reconnect().await {
+                break;
             }
         }
     }
@@ -197,37 +313,56 @@ where
 impl<V> WebsocketPool<V> {
     /// # New WebSocket Pool
     ///
-    /// Creates a new `WebsocketPool` with the specified number of connections.
+    /// Creates a new `WebsocketPool` with the specified number of connections to each
+    /// of the given endpoints.
     pub async fn new<F>(
         config: &ConnectionSettings,
+        urls: &[Url],
         extractor: F,
         shutdown: ShutDown,
+        stats: WebsocketStatsDB,
     ) -> BenchResult<Self>
     where
         F: Fn(LazyValue) -> Option<V> + Send + 'static + Clone,
         V: Send + 'static,
     {
         let count = config.ws_connections_count;
-        let mut connections = Vec::with_capacity(count);
-        for _ in 0..count {
-            let tx =
-                WsWorker::init(&config.ephem_url, extractor.clone(), shutdown.listener()).await?;
-            connections.push(tx);
+        let headers = http::headers(config)?;
+        let mut connections = Vec::with_capacity(urls.len());
+        for url in urls {
+            // The WebSocket upgrade is only supported over HTTP/1.1
+            let tls = tls::connector(url, &config.tls, &[ALPN_HTTP1])?;
+            let mut endpoint = Vec::with_capacity(count);
+            for _ in 0..count {
+                let tx = WsWorker::init(
+                    url,
+                    tls.clone(),
+                    headers.clone(),
+                    extractor.clone(),
+                    shutdown.listener(),
+                    stats.clone(),
+                )
+                .await?;
+                endpoint.push(tx);
+            }
+            connections.push(endpoint);
         }
         Ok(Self {
+            next: vec![0; connections.len()],
             connections,
-            next: 0,
         })
     }
 
     /// # Get Connection
     ///
-    /// Returns a sender for one of the WebSocket connections in the pool, using a
-    /// round-robin strategy to distribute the load.
-    pub fn connection(&mut self) -> Sender<Subscription<V>> {
-        let i = self.next;
-        self.next = (self.next + 1) % self.connections.len();
-        self.connections[i].clone()
+    /// Returns a sender for one of the WebSocket connections to the endpoint with the
+    /// given index, using a round-robin strategy to distribute the load.
+    pub fn connection(&mut self, endpoint: usize) -> Sender<Subscription<V>> {
+        let connections = &self.connections[endpoint];
+        let next = &mut self.next[endpoint];
+        let i = *next;
+        *next = (i + 1) % connections.len();
+        connections[i].clone()
     }
 }
 
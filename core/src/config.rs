@@ -1,8 +1,16 @@
-use crate::types::{AccountEncoding, AccountSize, BenchMode, BenchResult, ConnectionType, Url};
+use crate::types::{
+    AccountEncoding, AccountSelection, AccountSize, BenchMode, BenchResult, Commitment,
+    ConnectionType, EndpointSelection, PriorityFee, RateProfile, TransactionEncoding,
+    TransactionVersion, Url,
+};
 use pubkey::Pubkey;
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
-use std::path::PathBuf;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    time::Duration,
+};
 
 /// # Redline Configuration
 ///
@@ -31,6 +39,17 @@ pub struct Config {
     ///
     /// Configures the parameters for account data, such as encoding and size.
     pub data: DataSettings,
+    /// ## Search Settings
+    ///
+    /// Configures the saturation search, only required when running `redline search`.
+    #[serde(default)]
+    pub search: Option<SearchSettings>,
+    /// ## Trace Settings
+    ///
+    /// Configures the recording of the generated requests to a trace, and their
+    /// replay with `redline replay`.
+    #[serde(default)]
+    pub trace: Option<TraceSettings>,
     /// ## Parallelism
     ///
     /// Determines how many concurrent benchmarks to run, each on its own thread.
@@ -60,20 +79,119 @@ impl Config {
     /// * `path` - A `PathBuf` to the TOML configuration file.
     pub fn from_path(path: PathBuf) -> BenchResult<Self> {
         let config = std::fs::read_to_string(path)?;
-        toml::from_str(&config).map_err(Into::into)
+        let config: Self = toml::from_str(&config)?;
+        let benchmark = &config.benchmark;
+        if benchmark.iterations.is_none() && benchmark.duration.is_none() {
+            return Err("either benchmark.iterations or benchmark.duration must be set".into());
+        }
+        benchmark.mode.validate(benchmark.accounts_count)?;
+        if let Some(trace) = &config.trace {
+            if !(trace.speed().is_finite() && trace.speed() > 0.0) {
+                return Err("trace.speed must be positive".into());
+            }
+        }
+        // A batch holds the calls of a single mode, whose signatures, fee tiers and
+        // response extractor it shares, so the mixed modes are batched separately
+        let mixed = benchmark.mode_options.get("mixed");
+        if mixed.is_some_and(|options| options.batch_size() > 1) {
+            return Err(
+                "batch-size can't be set on the mixed mode, set it on the combined modes instead"
+                    .into(),
+            );
+        }
+        let connection = &config.connection;
+        // The signatures are confirmed through the endpoint, which the transaction was sent to
+        if !connection.ephem_ws_urls.is_empty()
+            && connection.ephem_ws_urls.len() != connection.ephem_urls().len()
+        {
+            return Err("ephem-ws-urls must have as many URLs as ephem-urls".into());
+        }
+        let confirmations = &config.confirmations;
+        // The signatures are confirmed in a single way, so that their latencies are comparable
+        if confirmations.subscribe_to_signatures && confirmations.get_signature_status {
+            return Err(
+                "subscribe-to-signatures and get-signature-status can't be enabled together".into(),
+            );
+        }
+        if confirmations.signature_status_interval().is_zero() {
+            return Err("signature-status-interval-ms must be positive".into());
+        }
+        let confirmed = confirmations.subscribe_to_signatures || confirmations.get_signature_status;
+        let keys = benchmark.mode.keys();
+        for (key, options) in &benchmark.mode_options {
+            // The options of the modes which aren't run, or misspelled ones, would be ignored
+            if !keys.contains(&key.as_str()) {
+                tracing::warn!(
+                    key,
+                    "mode-options are set for a mode which isn't run, ignoring"
+                );
+            }
+            if let Some(PriorityFee::Tiers(tiers)) = &options.priority_fee {
+                if tiers.is_empty() {
+                    return Err("priority-fee tiers can't be empty".into());
+                }
+                // The landing of the transactions of a tier is told by their signature
+                // confirmations
+                if !confirmed {
+                    return Err(
+                        "priority-fee tiers require subscribe-to-signatures or get-signature-status"
+                            .into(),
+                    );
+                }
+            }
+            if let Some(AccountSelection::Zipf { skew }) = options.account_selection {
+                if !(skew.is_finite() && skew >= 0.0) {
+                    return Err("the zipf skew of account-selection must be non-negative".into());
+                }
+            }
+            if let Some(PriorityFee::Uniform { min, max }) = options.priority_fee {
+                if min > max {
+                    return Err("the uniform priority-fee must satisfy min <= max".into());
+                }
+            }
+            if let Some(bytes) = options.heap_frame_bytes {
+                if bytes % 1024 != 0 || !(32 * 1024..=256 * 1024).contains(&bytes) {
+                    return Err(
+                        "heap-frame-bytes must be a multiple of 1024, between 32KB and 256KB"
+                            .into(),
+                    );
+                }
+            }
+        }
+        Ok(config)
     }
+}
 
-    /// # Load from Arguments
-    ///
-    /// Loads the configuration from a command-line argument.
-    /// Expects the first argument to be the path to the configuration file.
-    pub fn from_args() -> BenchResult<Self> {
-        let path = std::env::args()
-            .nth(1)
-            .ok_or("usage: redline config.toml")?
-            .into();
-        tracing::info!("using config file at {path:?} to run the benchmark");
-        Self::from_path(path)
+/// The name of the file in the keypairs directory, which holds the lookup tables.
+const LOOKUP_TABLES_FILE: &str = "lookup-tables.json";
+
+/// # Lookup Tables
+///
+/// The address lookup tables created by `assist prepare` for the v0 transactions, keyed
+/// by the base payer whose benchmark PDAs they hold, in the order of their seeds.
+#[serde_as]
+#[derive(Deserialize, Serialize, Default)]
+pub struct LookupTables(
+    #[serde_as(as = "HashMap<DisplayFromStr, DisplayFromStr>")] pub HashMap<Pubkey, Pubkey>,
+);
+
+impl LookupTables {
+    /// Loads the lookup tables from the keypairs directory, which is empty if
+    /// they were never created.
+    pub fn load(config: &Config) -> BenchResult<Self> {
+        let path = config.keypairs.join(LOOKUP_TABLES_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let tables = std::fs::read_to_string(path)?;
+        Ok(json::from_str(&tables)?)
+    }
+
+    /// Saves the lookup tables into the keypairs directory.
+    pub fn save(&self, config: &Config) -> BenchResult<()> {
+        let path = config.keypairs.join(LOOKUP_TABLES_FILE);
+        std::fs::write(path, json::to_string_pretty(self)?)?;
+        Ok(())
     }
 }
 
@@ -87,12 +205,143 @@ pub struct ConnectionSettings {
     pub chain_url: Url,
     /// The URL of the ephemeral node.
     pub ephem_url: Url,
+    /// The URLs of the ephemeral node replicas to spread the benchmark requests across.
+    /// Defaults to `ephem_url` alone.
+    #[serde(default)]
+    pub ephem_urls: Vec<Url>,
+    /// The policy for selecting the ephemeral endpoint for every request.
+    #[serde(default)]
+    pub endpoint_selection: EndpointSelection,
+    /// The WebSocket URL of the main chain node. Derived from `chain_url` if not set.
+    #[serde(default)]
+    pub chain_ws_url: Option<Url>,
+    /// The WebSocket URL of the ephemeral node. Derived from `ephem_url` if not set.
+    #[serde(default)]
+    pub ephem_ws_url: Option<Url>,
+    /// The WebSocket URLs of the ephemeral node replicas, in the order of `ephem_urls`,
+    /// for the signature confirmations of the requests sent to each of them. Derived
+    /// from `ephem_urls` if not set.
+    #[serde(default)]
+    pub ephem_ws_urls: Vec<Url>,
     /// The type of HTTP connection to use (`http1` or `http2`).
     pub http_connection_type: ConnectionType,
     /// The maximum number of HTTP connections to establish.
     pub http_connections_count: usize,
     /// The maximum number of WebSocket connections to establish.
     pub ws_connections_count: usize,
+    /// TLS settings for `https://` and `wss://` endpoints.
+    #[serde(default)]
+    pub tls: TlsSettings,
+    /// Extra headers to set on every HTTP request and WebSocket upgrade request to the
+    /// ephemeral endpoints, but not to the main chain node.
+    /// The values may reference environment variables as `${NAME}`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    /// Optional authentication for the ephemeral endpoints, sent along with the extra headers.
+    #[serde(default)]
+    pub auth: Option<Auth>,
+}
+
+impl ConnectionSettings {
+    /// Returns the URLs of the ephemeral endpoints to send the benchmark requests to.
+    pub fn ephem_urls(&self) -> Vec<Url> {
+        if self.ephem_urls.is_empty() {
+            vec![self.ephem_url.clone()]
+        } else {
+            self.ephem_urls.clone()
+        }
+    }
+
+    /// Returns the WebSocket URL of the main chain node.
+    pub fn chain_ws_url(&self) -> Url {
+        self.chain_ws_url
+            .clone()
+            .unwrap_or_else(|| self.chain_url.websocket())
+    }
+
+    /// Returns the WebSocket URL of the ephemeral node.
+    pub fn ephem_ws_url(&self) -> Url {
+        self.ephem_ws_url
+            .clone()
+            .unwrap_or_else(|| self.ephem_url.websocket())
+    }
+
+    /// Returns the WebSocket URLs of the ephemeral endpoints, one for each of the
+    /// URLs returned by `ephem_urls`.
+    pub fn ephem_ws_urls(&self) -> Vec<Url> {
+        if !self.ephem_ws_urls.is_empty() {
+            self.ephem_ws_urls.clone()
+        } else if self.ephem_urls.is_empty() {
+            vec![self.ephem_ws_url()]
+        } else {
+            self.ephem_urls.iter().map(Url::websocket).collect()
+        }
+    }
+
+    /// Returns the extra headers, including the authentication header if configured,
+    /// with the environment variables in their values expanded.
+    pub fn headers(&self) -> BenchResult<Vec<(String, String)>> {
+        let mut headers = Vec::with_capacity(self.headers.len() + 1);
+        for (name, value) in &self.headers {
+            headers.push((name.clone(), expand_env(value)?));
+        }
+        match &self.auth {
+            Some(Auth::Bearer(token)) => {
+                let value = format!("Bearer {}", expand_env(token)?);
+                headers.push(("authorization".into(), value));
+            }
+            Some(Auth::ApiKey(key)) => {
+                headers.push(("x-api-key".into(), expand_env(key)?));
+            }
+            None => {}
+        }
+        Ok(headers)
+    }
+}
+
+/// # Authentication
+///
+/// Credentials required by RPC providers, sent as a header with every request.
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum Auth {
+    /// A token, sent as `Authorization: Bearer <token>`.
+    Bearer(String),
+    /// A key, sent as `x-api-key: <key>`.
+    ApiKey(String),
+}
+
+/// Expands the `${NAME}` references to environment variables in the given value.
+fn expand_env(value: &str) -> BenchResult<String> {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            return Err(format!("unterminated environment variable in `{value}`").into());
+        };
+        let name = &rest[start + 2..start + end];
+        let var =
+            std::env::var(name).map_err(|_| format!("environment variable `{name}` is not set"))?;
+        expanded.push_str(&var);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// # TLS Settings
+///
+/// Configures the verification of the server certificates of TLS endpoints.
+#[derive(Deserialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct TlsSettings {
+    /// A path to a PEM bundle of CA certificates to trust, in addition to the web PKI roots.
+    #[serde(default)]
+    pub ca_bundle: Option<PathBuf>,
+    /// Skip the verification of the server certificates, e.g. for self-signed local nodes.
+    #[serde(default)]
+    pub insecure: bool,
 }
 
 /// # Benchmark Settings
@@ -104,11 +353,25 @@ pub struct BenchmarkSettings {
     /// ## Iterations
     ///
     /// The total number of requests or transactions to send.
-    pub iterations: u64,
+    /// Can be omitted if `duration` is set.
+    #[serde(default)]
+    pub iterations: Option<u64>,
+    /// ## Duration
+    ///
+    /// The wall-clock duration of the benchmark in seconds. If `iterations` is also
+    /// set, the benchmark stops at whichever limit is reached first.
+    #[serde(default)]
+    pub duration: Option<u64>,
     /// ## Rate
     ///
     /// The desired rate of requests or transactions per second (RPS/TPS).
     pub rate: u32,
+    /// ## Rate Profile
+    ///
+    /// An optional schedule for the target rate, which takes precedence over the
+    /// fixed `rate`, used to observe the validator's behavior under changing load.
+    #[serde(default)]
+    pub rate_profile: Option<RateProfile>,
     /// ## Concurrency
     ///
     /// The number of concurrent tasks to use for sending requests.
@@ -117,6 +380,25 @@ pub struct BenchmarkSettings {
     ///
     /// A flag to enable or disable the preflight check for transactions.
     pub preflight_check: bool,
+    /// ## Send Transaction Settings
+    ///
+    /// Configures the options of the `sendTransaction` requests.
+    #[serde(default)]
+    pub send_transaction: SendTransactionSettings,
+    /// ## Transaction Version
+    ///
+    /// The format of the benchmark transactions, either `legacy` or `v0`, the latter
+    /// referencing the benchmark PDAs through the address lookup tables.
+    #[serde(default)]
+    pub transaction_version: TransactionVersion,
+    /// ## Seed
+    ///
+    /// Seeds the generation of the workload, i.e. the account choices, payers, priority
+    /// fees and mode sequences, so that the same config produces the same requests.
+    /// Every thread derives its own generator from the seed plus the thread index.
+    /// Random if not set.
+    #[serde(default)]
+    pub seed: Option<u64>,
     /// ## Clone frequency
     ///
     /// The frequency in milliseconds, at which the account cloning should be triggered.
@@ -129,6 +411,81 @@ pub struct BenchmarkSettings {
     ///
     /// The benchmark mode to execute, which can be a single mode or a mix of modes.
     pub mode: BenchMode,
+    /// ## Mode Options
+    ///
+    /// Options for individual benchmark modes, keyed by the mode name, e.g. `get-account-info`.
+    #[serde(default)]
+    pub mode_options: HashMap<String, ModeOptions>,
+}
+
+impl BenchmarkSettings {
+    /// Returns the configured benchmark duration, if any.
+    pub fn duration(&self) -> Option<Duration> {
+        self.duration.map(Duration::from_secs)
+    }
+
+    /// Returns the options for the given benchmark mode, or the defaults if not configured.
+    pub fn mode_options(&self, mode: &BenchMode) -> ModeOptions {
+        self.mode_options
+            .get(mode.key())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// # Mode Options
+///
+/// Options which apply to a single benchmark mode.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ModeOptions {
+    /// The number of calls to pack into a single JSON-RPC batch request. Defaults to 1,
+    /// i.e. no batching.
+    #[serde(default)]
+    pub batch_size: Option<usize>,
+    /// The compute unit limit of the transactions, set with `SetComputeUnitLimit`.
+    #[serde(default)]
+    pub compute_unit_limit: Option<u32>,
+    /// The priority fee of the transactions, set with `SetComputeUnitPrice`.
+    #[serde(default)]
+    pub priority_fee: Option<PriorityFee>,
+    /// The heap frame size of the transactions in bytes, requested with `RequestHeapFrame`.
+    /// Should be a multiple of 1024, between 32KB and 256KB.
+    #[serde(default)]
+    pub heap_frame_bytes: Option<u32>,
+    /// The strategy for selecting the accounts of every request. Defaults to `uniform` for
+    /// the transaction-based modes, and to `sequential` for the RPC-based ones.
+    #[serde(default)]
+    pub account_selection: Option<AccountSelection>,
+}
+
+impl ModeOptions {
+    /// Returns the number of calls in every request of the mode.
+    pub fn batch_size(&self) -> usize {
+        self.batch_size.unwrap_or(1).max(1)
+    }
+}
+
+/// # Send Transaction Settings
+///
+/// Configures the options of the `sendTransaction` requests, which control how the
+/// RPC checks, retries and forwards the transactions.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct SendTransactionSettings {
+    /// The encoding of the serialized transactions. Defaults to `base64`.
+    #[serde(default)]
+    pub encoding: TransactionEncoding,
+    /// The maximum number of times the RPC retries sending the transaction.
+    /// Defaults to the retry policy of the RPC.
+    #[serde(default)]
+    pub max_retries: Option<usize>,
+    /// The minimum slot at which the RPC may perform the preflight check.
+    #[serde(default)]
+    pub min_context_slot: Option<u64>,
+    /// Overrides the commitment level of the preflight check.
+    #[serde(default)]
+    pub preflight_commitment: Option<Commitment>,
 }
 
 /// # Confirmation Settings
@@ -143,10 +500,129 @@ pub struct ConfirmationSettings {
     pub subscribe_to_signatures: bool,
     /// Use `getSignatureStatuses` for transaction confirmations.
     pub get_signature_status: bool,
+    /// The interval in milliseconds, at which outstanding signatures are polled
+    /// with `getSignatureStatuses`. Defaults to 100ms.
+    #[serde(default)]
+    pub signature_status_interval_ms: Option<u64>,
+    /// The commitment level for subscriptions, reads and preflight checks. Defaults to `processed`.
+    #[serde(default)]
+    pub commitment: Commitment,
+    /// Overrides the commitment level of account subscriptions.
+    #[serde(default)]
+    pub account_commitment: Option<Commitment>,
+    /// Overrides the commitment level of signature subscriptions and status polling.
+    #[serde(default)]
+    pub signature_commitment: Option<Commitment>,
+    /// The time in milliseconds to wait for a confirmation, before counting it as timed
+    /// out. Defaults to a timeout scaled by the commitment level of the confirmation.
+    #[serde(default)]
+    pub confirmation_timeout_ms: Option<u64>,
     /// Enforce total synchronization, ensuring all confirmations are received before completing a transaction.
     pub enforce_total_sync: bool,
 }
 
+impl ConfirmationSettings {
+    /// Returns the interval at which outstanding signatures are polled.
+    pub fn signature_status_interval(&self) -> Duration {
+        Duration::from_millis(self.signature_status_interval_ms.unwrap_or(100))
+    }
+
+    /// Returns the commitment level at which account updates are reported.
+    pub fn account_commitment(&self) -> Commitment {
+        self.account_commitment.unwrap_or(self.commitment)
+    }
+
+    /// Returns the commitment level at which signatures are confirmed.
+    pub fn signature_commitment(&self) -> Commitment {
+        self.signature_commitment.unwrap_or(self.commitment)
+    }
+
+    /// Returns how long to wait for an account update confirmation.
+    pub fn account_timeout(&self) -> Duration {
+        self.timeout(self.account_commitment())
+    }
+
+    /// Returns how long to wait for a signature confirmation.
+    pub fn signature_timeout(&self) -> Duration {
+        self.timeout(self.signature_commitment())
+    }
+
+    /// Returns the configured confirmation timeout, or the default one for the given
+    /// commitment level: most processed confirmations arrive within 500ms, while the
+    /// finalization of a block on a Solana cluster takes around 13s.
+    fn timeout(&self, commitment: Commitment) -> Duration {
+        if let Some(ms) = self.confirmation_timeout_ms {
+            return Duration::from_millis(ms);
+        }
+        match commitment {
+            Commitment::Processed => Duration::from_secs(3),
+            Commitment::Confirmed => Duration::from_secs(10),
+            Commitment::Finalized => Duration::from_secs(30),
+        }
+    }
+}
+
+/// # Search Settings
+///
+/// Configures the saturation search, which runs a series of short trials, bisecting
+/// on the rate, to find the highest rate the validator can sustain while meeting the SLO.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct SearchSettings {
+    /// The lowest rate to consider, per benchmark thread.
+    pub min_rate: u32,
+    /// The highest rate to consider, per benchmark thread.
+    pub max_rate: u32,
+    /// The duration of each trial, in seconds.
+    pub trial_duration: u64,
+    /// The search stops once the distance between the highest passing
+    /// and the lowest failing rate is no larger than this value.
+    pub resolution: u32,
+    /// ## SLO: Latency
+    ///
+    /// The maximum 95th percentile of signature confirmation latency, in milliseconds.
+    /// For modes without signatures, it's applied to the request latency instead.
+    pub max_p95_latency_ms: u32,
+    /// ## SLO: Error Rate
+    ///
+    /// The maximum percentage of requests which are allowed to fail, or to time out
+    /// waiting for a confirmation.
+    pub max_error_rate: f64,
+}
+
+/// # Trace Settings
+///
+/// Configures the trace of the request stream, which is recorded by a benchmark run,
+/// and re-sent by `redline replay` with the original timing, or scaled by the speed.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct TraceSettings {
+    /// The directory of the trace, holding a file per benchmark thread.
+    pub path: PathBuf,
+    /// Whether to record the requests of the benchmark runs to the trace.
+    #[serde(default)]
+    pub record: bool,
+    /// The speed of the replay relative to the original timing, e.g. 2.0 for twice
+    /// as fast. Defaults to 1.0.
+    #[serde(default)]
+    pub speed: Option<f64>,
+    /// Whether the run replays the trace, set by `redline replay`.
+    #[serde(default, skip_deserializing)]
+    pub replay: bool,
+}
+
+impl TraceSettings {
+    /// Returns the path of the trace file of the benchmark thread with the given index.
+    pub fn file(&self, thread: usize) -> PathBuf {
+        self.path.join(format!("{thread}.trace"))
+    }
+
+    /// Returns the speed of the replay relative to the original timing.
+    pub fn speed(&self) -> f64 {
+        self.speed.unwrap_or(1.0)
+    }
+}
+
 /// # Data Settings
 ///
 /// Configures the parameters for account data used in the benchmark.
@@ -1,126 +1,102 @@
 //! Statistical aggregation for benchmark observations.
 //!
-//! Uses streaming algorithms (Welford's for mean/variance, reservoir sampling
-//! for percentiles) to efficiently process millions of latency measurements
-//! with minimal memory overhead.
+//! Records observations into high-dynamic-range histograms, which keep a
+//! fixed relative precision over the whole range of values. Histograms are
+//! mergeable, so statistics collected by parallel threads can be combined
+//! exactly, and are serialized along with the results, so any quantile can
+//! be recomputed after the fact.
 
+use base64::{prelude::BASE64_STANDARD, Engine};
+use hdrhistogram::serialization::{
+    Deserializer as HdrDeserializer, Serializer as _, V2DeflateSerializer,
+};
 use json::{Deserialize, Serialize};
-use rand::{rngs::ThreadRng, thread_rng, Rng};
-use std::collections::HashMap;
+use serde::{de, Deserializer, Serializer};
+use std::collections::{BTreeMap, HashMap};
+
+/// The number of significant decimal digits preserved by the histograms.
+const HISTOGRAM_PRECISION: u8 = 3;
 
 /// # Streaming Statistics
 ///
-/// Collects observations using Welford's algorithm for mean/variance and reservoir
-/// sampling for percentiles. Memory-efficient for millions of observations.
+/// Collects observations into a histogram, with a constant memory footprint
+/// regardless of the number of observations.
 #[derive(Debug)]
 pub struct StreamingStats {
-    count: usize,
-    mean: f64,
-    m2: f64, // Sum of squared deviations for variance calculation
-    min: u32,
-    max: u32,
-    reservoir: Vec<u32>, // Reservoir sampling for percentile estimation
-    reservoir_size: usize,
-    rng: ThreadRng,
+    histogram: Histogram,
 }
 
 impl StreamingStats {
-    const DEFAULT_RESERVOIR_SIZE: usize = 10_000;
-
-    /// Creates a new `StreamingStats` with default reservoir size (10K samples).
+    /// Creates a new, empty `StreamingStats`.
     pub fn new() -> Self {
-        Self::with_reservoir_size(Self::DEFAULT_RESERVOIR_SIZE)
-    }
-
-    /// Creates a new `StreamingStats` with specified reservoir size.
-    pub fn with_reservoir_size(reservoir_size: usize) -> Self {
         Self {
-            count: 0,
-            mean: 0.0,
-            m2: 0.0,
-            min: u32::MAX,
-            max: 0,
-            reservoir: Vec::with_capacity(reservoir_size),
-            reservoir_size,
-            rng: thread_rng(),
+            histogram: Histogram::default(),
         }
     }
 
-    /// Adds a new observation using Welford's online algorithm.
+    /// Adds a new observation.
     pub fn push(&mut self, value: u32) {
-        // Welford's online algorithm for mean and variance
-        self.count += 1;
-        let delta = value as f64 - self.mean;
-        self.mean += delta / self.count as f64;
-        self.m2 += delta * (value as f64 - self.mean);
-
-        // Track min/max
-        self.min = self.min.min(value);
-        self.max = self.max.max(value);
-
-        // Reservoir sampling for percentiles
-        if self.reservoir.len() < self.reservoir_size {
-            self.reservoir.push(value);
-        } else {
-            let j = self.rng.gen_range(0..self.count);
-            if j < self.reservoir_size {
-                self.reservoir[j] = value;
-            }
-        }
+        self.histogram.0.saturating_record(value as u64);
     }
 
     /// Finalizes the statistics and returns `ObservationsStats`.
-    pub fn finalize(mut self, invertedq: bool) -> ObservationsStats {
-        if self.count == 0 {
-            return ObservationsStats::default();
-        }
+    ///
+    /// With `invertedq`, the quantiles are inverted, i.e. the 95th percentile is
+    /// the value exceeded by 95% of observations, which suits metrics like
+    /// throughput, where lower values are worse.
+    pub fn finalize(self, invertedq: bool) -> ObservationsStats {
+        ObservationsStats::from_histogram(self.histogram, invertedq)
+    }
+}
 
-        // Sort reservoir for percentile calculation
-        self.reservoir.sort_unstable();
+impl Default for StreamingStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
-        let avg = self.mean as i32;
-        let median = if !self.reservoir.is_empty() {
-            self.reservoir[self.reservoir.len() / 2] as i32
-        } else {
-            avg
-        };
+/// # Histogram
+///
+/// A mergeable high-dynamic-range histogram of observations. It's serialized
+/// as a base64 string of its compressed V2 encoding (the HdrHistogram format).
+#[derive(Debug, Clone)]
+pub struct Histogram(pub hdrhistogram::Histogram<u64>);
 
-        // Calculate 95th percentile from reservoir
-        let q95_count = (self.reservoir.len() as f64 * 0.95).ceil() as usize;
-        let p95_idx = if invertedq {
-            self.reservoir.len().saturating_sub(q95_count + 1)
-        } else {
-            q95_count.saturating_sub(1).min(self.reservoir.len() - 1)
-        };
-        let quantile95 = if !self.reservoir.is_empty() {
-            self.reservoir[p95_idx] as i32
-        } else {
-            avg
-        };
+impl Default for Histogram {
+    fn default() -> Self {
+        let histogram = hdrhistogram::Histogram::new(HISTOGRAM_PRECISION)
+            .expect("histogram precision should be valid");
+        Self(histogram)
+    }
+}
 
-        // Calculate standard deviation from variance
-        let variance = if self.count > 1 {
-            self.m2 / self.count as f64
-        } else {
-            0.0
-        };
-        let stddev = variance.sqrt() as u32;
-
-        ObservationsStats {
-            count: self.count,
-            median,
-            min: self.min,
-            max: self.max,
-            avg,
-            quantile95,
-            stddev,
-        }
+impl Histogram {
+    /// Merges the observations of another histogram into this one.
+    pub fn add(&mut self, other: &Self) {
+        self.0
+            .add(&other.0)
+            .expect("auto-resizing histograms should always be mergeable");
     }
 }
 
-impl Default for StreamingStats {
-    fn default() -> Self {
-        Self::new()
+impl Serialize for Histogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut buffer = Vec::new();
+        V2DeflateSerializer::new()
+            .serialize(&self.0, &mut buffer)
+            .map_err(|e| serde::ser::Error::custom(format!("{e:?}")))?;
+        serializer.serialize_str(&BASE64_STANDARD.encode(buffer))
+    }
+}
+
+impl<'de> Deserialize<'de> for Histogram {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let encoded = String::deserialize(deserializer)?;
+        let decoded = BASE64_STANDARD.decode(encoded).map_err(de::Error::custom)?;
+        HdrDeserializer::new()
+            .deserialize(&mut decoded.as_slice())
+            .map(Self)
+            .map_err(|e| de::Error::custom(format!("{e:?}")))
     }
 }
 
@@ -139,23 +115,286 @@ pub struct BenchStatistics {
     pub signature_confirmation_latency: ObservationsStats,
     /// Latency for receiving account updates.
     pub account_update_latency: ObservationsStats,
+    /// Request latencies for each benchmark mode, measured from the intended send time
+    /// of each request, i.e. corrected for coordinated omission.
+    #[serde(default)]
+    pub corrected_request_stats: HashMap<String, ObservationsStats>,
+    /// Latency for receiving signature confirmations, corrected for coordinated omission.
+    #[serde(default)]
+    pub corrected_signature_confirmation_latency: ObservationsStats,
+    /// Latency for receiving account updates, corrected for coordinated omission.
+    #[serde(default)]
+    pub corrected_account_update_latency: ObservationsStats,
     /// Throughput statistics for the entire benchmark run.
     pub rps: ObservationsStats,
+    /// Error and timeout counters for each benchmark mode.
+    #[serde(default)]
+    pub errors: HashMap<String, ErrorStats>,
+    /// Per-second breakdown of the benchmark run.
+    #[serde(default)]
+    pub timeline: Vec<TimelineBucket>,
+    /// Counters for the health of the WebSocket connections.
+    #[serde(default)]
+    pub websocket: WebsocketStats,
+    /// Breakdown of the requests by the ephemeral endpoint they were sent to.
+    #[serde(default)]
+    pub endpoints: HashMap<String, EndpointStats>,
+    /// Breakdown of the transactions by the priority fee tier of their payers.
+    #[serde(default)]
+    pub tiers: Vec<TierStats>,
+    /// The undelegation and re-delegation cycles of the accounts, undelegated by
+    /// the commit-and-undelegate mode.
+    #[serde(default)]
+    pub redelegation: RedelegationStats,
 }
 
-/// # Observation Statistics
+/// # Timeline Bucket
 ///
-/// A detailed breakdown of a set of observations, including count, median, min, max, average, 95th percentile, and standard deviation.
+/// Observations collected during a single second of the benchmark run. Latencies
+/// are attributed to the second in which the corresponding request was sent.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct TimelineBucket {
+    /// Offset of the bucket from the start of the benchmark, in seconds.
+    pub second: u64,
+    /// The rate which was targeted during this second.
+    pub target_rate: u32,
+    /// The rate which was actually achieved during this second.
+    pub achieved_rate: u32,
+    /// Request latencies for each benchmark mode.
+    #[serde(default)]
+    pub request_latency: HashMap<String, ObservationsStats>,
+    /// Latency for receiving signature confirmations.
+    #[serde(default)]
+    pub signature_confirmation_latency: ObservationsStats,
+    /// Latency for receiving account updates.
+    #[serde(default)]
+    pub account_update_latency: ObservationsStats,
+    /// The number of requests which failed, or timed out waiting for a confirmation.
+    #[serde(default)]
+    pub errors: u64,
+}
+
+impl TimelineBucket {
+    /// # Merge Timeline Buckets
+    ///
+    /// Merges the buckets for the same second, collected by parallel threads.
+    pub fn merge(buckets: Vec<Self>) -> Self {
+        let mut merged = Self::default();
+        let mut request_latency = HashMap::<String, Vec<ObservationsStats>>::new();
+        let mut signature_confirmation_latency = Vec::new();
+        let mut account_update_latency = Vec::new();
+        for bucket in buckets {
+            merged.second = bucket.second;
+            merged.target_rate += bucket.target_rate;
+            merged.achieved_rate += bucket.achieved_rate;
+            merged.errors += bucket.errors;
+            for (key, value) in bucket.request_latency {
+                request_latency.entry(key).or_default().push(value);
+            }
+            signature_confirmation_latency.push(bucket.signature_confirmation_latency);
+            account_update_latency.push(bucket.account_update_latency);
+        }
+        merged.request_latency = request_latency
+            .into_iter()
+            .map(|(key, value)| (key, ObservationsStats::merge(value)))
+            .collect();
+        merged.signature_confirmation_latency =
+            ObservationsStats::merge(signature_confirmation_latency);
+        merged.account_update_latency = ObservationsStats::merge(account_update_latency);
+        merged
+    }
+}
+
+/// # Endpoint Statistics
+///
+/// Observations for the requests sent to a single ephemeral endpoint, across all modes.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct EndpointStats {
+    /// Latency of the requests sent to the endpoint.
+    pub request_latency: ObservationsStats,
+    /// Latency for receiving the signature confirmations of the transactions, which
+    /// landed through the endpoint.
+    #[serde(default)]
+    pub signature_confirmation_latency: ObservationsStats,
+    /// Error and timeout counters of the requests sent to the endpoint.
+    pub errors: ErrorStats,
+}
+
+/// # Fee Tier Statistics
+///
+/// Observations for the transactions sent by the payers of a single priority fee tier.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct TierStats {
+    /// The compute unit price of the tier, in micro-lamports.
+    pub priority_fee: u64,
+    /// The number of transactions which were sent.
+    pub sent: u64,
+    /// The number of transactions which were confirmed to have executed successfully.
+    pub landed: u64,
+    /// Latency for receiving the signature confirmations of the landed transactions.
+    pub signature_confirmation_latency: ObservationsStats,
+}
+
+/// # Re-delegation Statistics
+///
+/// Observations of the cycles of the accounts, which are undelegated from the ER by the
+/// commit-and-undelegate mode, and delegated to it again on the base chain.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct RedelegationStats {
+    /// The number of accounts which were sent to be undelegated.
+    pub undelegated: u64,
+    /// The number of accounts which were delegated again, completing the cycle.
+    pub redelegated: u64,
+    /// The number of accounts whose undelegation didn't land on the base chain in time.
+    pub timeouts: u64,
+    /// The number of accounts whose cycle was still in progress at the end of the benchmark,
+    /// which might be left undelegated.
+    pub in_progress: u64,
+    /// The number of requests which weren't sent, since all of the accounts they could
+    /// use were in the cycle.
+    pub starved: u64,
+    /// Latency of the full cycle, from sending the undelegation, to the landing of
+    /// the re-delegation on the base chain.
+    pub cycle_latency: ObservationsStats,
+}
+
+impl TierStats {
+    /// Returns the percentage of sent transactions which landed.
+    pub fn landing_rate(&self) -> f64 {
+        if self.sent == 0 {
+            return 0.0;
+        }
+        self.landed as f64 / self.sent as f64 * 100.0
+    }
+}
+
+/// # WebSocket Statistics
+///
+/// Counters for the health of the WebSocket connections used for subscriptions.
 #[derive(Deserialize, Serialize, Clone, Copy, Default)]
 #[serde(rename_all = "kebab-case")]
+pub struct WebsocketStats {
+    /// The number of times a dropped connection was re-established.
+    pub reconnects: u64,
+    /// The number of oneshot subscriptions which were dropped along with the connection.
+    pub lost_subscriptions: u64,
+}
+
+impl WebsocketStats {
+    /// Adds up the counters of another `WebsocketStats` into this one.
+    pub fn add(&mut self, other: Self) {
+        self.reconnects += other.reconnects;
+        self.lost_subscriptions += other.lost_subscriptions;
+    }
+}
+
+/// # Error Statistics
+///
+/// Counters for the outcomes of the requests of a single benchmark mode.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct ErrorStats {
+    /// The number of requests which were successfully delivered.
+    pub delivered: u64,
+    /// The number of requests which failed to be delivered, or were rejected by the RPC.
+    pub rpc_errors: u64,
+    /// A breakdown of the RPC errors, by the JSON-RPC error code and message.
+    pub rpc_error_breakdown: HashMap<String, u64>,
+    /// The number of transactions which were delivered, but failed to execute.
+    pub executed_with_error: u64,
+    /// The number of account updates which weren't received in time.
+    pub account_update_timeouts: u64,
+    /// The number of signature confirmations which weren't received in time.
+    pub signature_timeouts: u64,
+    /// The number of requests which failed in any way, each counted once, even if it
+    /// both timed out and failed to execute. Missing from the results of older versions.
+    #[serde(default)]
+    pub failed: u64,
+    /// The number of delivered transactions, for which the RPC returned a signature other
+    /// than the one they were signed with.
+    #[serde(default)]
+    pub signature_mismatches: u64,
+}
+
+impl ErrorStats {
+    /// Records an RPC error, under the given description in the breakdown, which fails
+    /// the request.
+    pub fn rpc_error(&mut self, description: String) {
+        self.rpc_errors += 1;
+        self.failed += 1;
+        *self.rpc_error_breakdown.entry(description).or_default() += 1;
+    }
+
+    /// Adds up the counters of another `ErrorStats` into this one.
+    pub fn add(&mut self, other: Self) {
+        self.delivered += other.delivered;
+        self.rpc_errors += other.rpc_errors;
+        for (description, count) in other.rpc_error_breakdown {
+            *self.rpc_error_breakdown.entry(description).or_default() += count;
+        }
+        self.executed_with_error += other.executed_with_error;
+        self.account_update_timeouts += other.account_update_timeouts;
+        self.signature_timeouts += other.signature_timeouts;
+        self.failed += other.failed;
+        self.signature_mismatches += other.signature_mismatches;
+    }
+
+    /// Returns the number of requests which were sent.
+    pub fn sent(&self) -> u64 {
+        self.delivered + self.rpc_errors
+    }
+
+    /// Returns the number of failures of any kind, including timeouts.
+    pub fn failures(&self) -> u64 {
+        self.rpc_errors
+            + self.executed_with_error
+            + self.account_update_timeouts
+            + self.signature_timeouts
+    }
+
+    /// Returns the percentage of sent requests which failed in any way.
+    pub fn error_rate(&self) -> f64 {
+        if self.sent() == 0 {
+            return 0.0;
+        }
+        // The results of older versions only count the failures, which might
+        // add up to more than one per request
+        let failed = match self.failed {
+            0 => self.failures().min(self.sent()),
+            failed => failed,
+        };
+        failed as f64 / self.sent() as f64 * 100.0
+    }
+}
+
+/// # Observation Statistics
+///
+/// A detailed breakdown of a set of observations, including count, median, min, max, average,
+/// 90th/95th/99th/99.9th percentiles, and standard deviation, along with the full histogram.
+#[derive(Deserialize, Serialize, Clone, Default)]
+#[serde(rename_all = "kebab-case")]
 pub struct ObservationsStats {
     pub count: usize,
     pub median: i32,
     pub min: u32,
     pub max: u32,
     pub avg: i32,
+    #[serde(default)]
+    pub quantile90: i32,
     pub quantile95: i32,
+    #[serde(default)]
+    pub quantile99: i32,
+    #[serde(default)]
+    pub quantile999: i32,
     pub stddev: u32,
+    /// The full histogram of observations, absent for statistics which cannot
+    /// be represented by one, e.g. those summed up across parallel threads.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Histogram>,
 }
 
 impl BenchStatistics {
@@ -168,9 +407,19 @@ impl BenchStatistics {
         }
         let configuration = std::mem::take(&mut stats.first_mut().unwrap().configuration);
         let mut request_stats = HashMap::new();
+        let mut corrected_request_stats = HashMap::new();
         let mut rps = Vec::new();
         let mut account_update_stats = Vec::new();
         let mut signature_confirmation_stats = Vec::new();
+        let mut corrected_account_update_stats = Vec::new();
+        let mut corrected_signature_confirmation_stats = Vec::new();
+        let mut timeline = BTreeMap::<u64, Vec<TimelineBucket>>::new();
+        let mut errors = HashMap::<String, ErrorStats>::new();
+        let mut websocket = WebsocketStats::default();
+        let mut endpoints = HashMap::<String, (Vec<_>, Vec<_>, ErrorStats)>::new();
+        let mut tiers = BTreeMap::<u64, (u64, u64, Vec<ObservationsStats>)>::new();
+        let mut redelegation = RedelegationStats::default();
+        let mut cycle_latency = Vec::new();
 
         for s in stats {
             for (key, value) in s.request_stats {
@@ -179,69 +428,273 @@ impl BenchStatistics {
                     .or_insert_with(Vec::new)
                     .push(value);
             }
+            for (key, value) in s.corrected_request_stats {
+                corrected_request_stats
+                    .entry(key)
+                    .or_insert_with(Vec::new)
+                    .push(value);
+            }
             account_update_stats.push(s.account_update_latency);
             signature_confirmation_stats.push(s.signature_confirmation_latency);
+            corrected_account_update_stats.push(s.corrected_account_update_latency);
+            corrected_signature_confirmation_stats.push(s.corrected_signature_confirmation_latency);
             rps.push(s.rps);
+            for (key, value) in s.errors {
+                errors.entry(key).or_default().add(value);
+            }
+            for bucket in s.timeline {
+                timeline.entry(bucket.second).or_default().push(bucket);
+            }
+            websocket.add(s.websocket);
+            for (url, stats) in s.endpoints {
+                let (latency, signatures, errors) = endpoints.entry(url).or_default();
+                latency.push(stats.request_latency);
+                signatures.push(stats.signature_confirmation_latency);
+                errors.add(stats.errors);
+            }
+            for tier in s.tiers {
+                let (sent, landed, latency) = tiers.entry(tier.priority_fee).or_default();
+                *sent += tier.sent;
+                *landed += tier.landed;
+                latency.push(tier.signature_confirmation_latency);
+            }
+            redelegation.undelegated += s.redelegation.undelegated;
+            redelegation.redelegated += s.redelegation.redelegated;
+            redelegation.timeouts += s.redelegation.timeouts;
+            redelegation.in_progress += s.redelegation.in_progress;
+            redelegation.starved += s.redelegation.starved;
+            cycle_latency.push(s.redelegation.cycle_latency);
         }
+        redelegation.cycle_latency = ObservationsStats::merge(cycle_latency);
 
         let request_stats = request_stats
             .into_iter()
-            .map(|(key, value)| (key, ObservationsStats::merge(value, true)))
+            .map(|(key, value)| (key, ObservationsStats::merge(value)))
+            .collect();
+        let corrected_request_stats = corrected_request_stats
+            .into_iter()
+            .map(|(key, value)| (key, ObservationsStats::merge(value)))
             .collect();
 
         Self {
             configuration,
-            account_update_latency: ObservationsStats::merge(account_update_stats, true),
-            signature_confirmation_latency: ObservationsStats::merge(
-                signature_confirmation_stats,
-                true,
-            ),
+            account_update_latency: ObservationsStats::merge(account_update_stats),
+            signature_confirmation_latency: ObservationsStats::merge(signature_confirmation_stats),
             request_stats,
-            rps: ObservationsStats::merge(rps, false),
+            corrected_account_update_latency: ObservationsStats::merge(
+                corrected_account_update_stats,
+            ),
+            corrected_signature_confirmation_latency: ObservationsStats::merge(
+                corrected_signature_confirmation_stats,
+            ),
+            corrected_request_stats,
+            rps: ObservationsStats::sum(rps),
+            errors,
+            timeline: timeline.into_values().map(TimelineBucket::merge).collect(),
+            websocket,
+            endpoints: endpoints
+                .into_iter()
+                .map(|(url, (latency, signatures, errors))| {
+                    let stats = EndpointStats {
+                        request_latency: ObservationsStats::merge(latency),
+                        signature_confirmation_latency: ObservationsStats::merge(signatures),
+                        errors,
+                    };
+                    (url, stats)
+                })
+                .collect(),
+            tiers: tiers
+                .into_iter()
+                .map(|(priority_fee, (sent, landed, latency))| TierStats {
+                    priority_fee,
+                    sent,
+                    landed,
+                    signature_confirmation_latency: ObservationsStats::merge(latency),
+                })
+                .collect(),
+            redelegation,
+        }
+    }
+
+    /// Returns the percentage of sent requests which failed in any way, across all modes.
+    pub fn error_rate(&self) -> f64 {
+        let mut total = ErrorStats::default();
+        for errors in self.errors.values() {
+            total.add(errors.clone());
+        }
+        total.error_rate()
+    }
+
+    /// Returns the 95th percentile of the latency, which is subject to the search SLO: the
+    /// signature confirmation latency if it was observed, otherwise the worst request latency.
+    pub fn slo_latency(&self) -> i32 {
+        if self.signature_confirmation_latency.count > 0 {
+            return self.signature_confirmation_latency.quantile95;
         }
+        self.request_stats
+            .values()
+            .map(|s| s.quantile95)
+            .max()
+            .unwrap_or_default()
     }
 }
 
+/// # Search Report
+///
+/// The outcome of a saturation search, along with the statistics of every trial.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SearchReport {
+    /// The highest rate (per benchmark thread) which met the SLO, if any did.
+    pub knee: Option<u32>,
+    /// All the trials, in order of execution.
+    pub trials: Vec<SearchTrial>,
+}
+
+/// # Search Trial
+///
+/// A single trial of the saturation search, run at a fixed rate.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SearchTrial {
+    /// The target rate of the trial, per benchmark thread.
+    pub rate: u32,
+    /// Whether the trial met the SLO.
+    pub passed: bool,
+    /// The 95th percentile of the latency subject to the SLO, in microseconds.
+    pub latency95: i32,
+    /// The percentage of requests which failed.
+    pub error_rate: f64,
+    /// The full statistics of the trial.
+    pub stats: BenchStatistics,
+}
+
 impl ObservationsStats {
-    /// # Merge Observation Statistics
+    /// # From Histogram
     ///
-    /// Merges a vector of `ObservationsStats` into a single, consolidated report.
-    pub fn merge(stats: Vec<ObservationsStats>, average: bool) -> Self {
-        let total_count = if average { stats.len() } else { 1 };
-        if total_count == 0 {
+    /// Calculates the statistics from a histogram of observations, optionally with inverted quantiles.
+    pub fn from_histogram(histogram: Histogram, invertedq: bool) -> Self {
+        let h = &histogram.0;
+        if h.is_empty() {
             return Self::default();
         }
-        let sum = stats.iter().fold(
-            (0usize, 0i32, u32::MAX, 0u32, 0i32, 0i32, 0u32),
-            |acc, stat| {
-                (
-                    acc.0 + stat.count,
-                    acc.1 + stat.median,
-                    if average {
-                        acc.2.min(stat.min)
-                    } else {
-                        acc.2 + stat.min
-                    },
-                    if average {
-                        acc.3.max(stat.max)
-                    } else {
-                        acc.3 + stat.max
-                    },
-                    acc.4 + stat.avg,
-                    acc.5 + stat.quantile95,
-                    acc.6 + stat.stddev,
-                )
-            },
-        );
-
-        ObservationsStats {
-            count: sum.0,
-            median: sum.1 / total_count as i32,
-            min: sum.2,
-            max: sum.3,
-            avg: sum.4 / total_count as i32,
-            quantile95: sum.5 / total_count as i32,
-            stddev: sum.6 / total_count as u32,
+        let quantile = |q: f64| {
+            let q = if invertedq { 1.0 - q } else { q };
+            h.value_at_quantile(q) as i32
+        };
+        Self {
+            count: h.len() as usize,
+            median: quantile(0.5),
+            min: h.min() as u32,
+            max: h.max() as u32,
+            avg: h.mean() as i32,
+            quantile90: quantile(0.9),
+            quantile95: quantile(0.95),
+            quantile99: quantile(0.99),
+            quantile999: quantile(0.999),
+            stddev: h.stdev() as u32,
+            histogram: Some(histogram),
+        }
+    }
+
+    /// # Recompute Quantile
+    ///
+    /// Returns the value at the given quantile (0.0-1.0), if the histogram is available.
+    pub fn quantile(&self, quantile: f64) -> Option<i32> {
+        let histogram = self.histogram.as_ref()?;
+        Some(histogram.0.value_at_quantile(quantile) as i32)
+    }
+
+    /// # Merge Observation Statistics
+    ///
+    /// Merges a vector of `ObservationsStats` into a single, consolidated report, by
+    /// merging the underlying histograms, which yields exact merged quantiles.
+    pub fn merge(stats: Vec<ObservationsStats>) -> Self {
+        let mut merged: Option<Histogram> = None;
+        for histogram in stats.into_iter().filter_map(|s| s.histogram) {
+            match &mut merged {
+                Some(m) => m.add(&histogram),
+                None => merged = Some(histogram),
+            }
         }
+        merged
+            .map(|h| Self::from_histogram(h, false))
+            .unwrap_or_default()
+    }
+
+    /// # Sum Observation Statistics
+    ///
+    /// Sums up a vector of `ObservationsStats` field by field. Used for metrics like
+    /// throughput, which add up across parallel threads, rather than combine. The
+    /// result carries no histogram, as the sum of distributions isn't their merge.
+    pub fn sum(stats: Vec<ObservationsStats>) -> Self {
+        stats.iter().fold(Self::default(), |acc, stat| Self {
+            count: acc.count + stat.count,
+            median: acc.median + stat.median,
+            min: acc.min + stat.min,
+            max: acc.max + stat.max,
+            avg: acc.avg + stat.avg,
+            quantile90: acc.quantile90 + stat.quantile90,
+            quantile95: acc.quantile95 + stat.quantile95,
+            quantile99: acc.quantile99 + stat.quantile99,
+            quantile999: acc.quantile999 + stat.quantile999,
+            stddev: acc.stddev + stat.stddev,
+            histogram: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(values: impl Iterator<Item = u32>) -> ObservationsStats {
+        let mut stats = StreamingStats::new();
+        values.for_each(|v| stats.push(v));
+        stats.finalize(false)
+    }
+
+    #[test]
+    fn merged_quantiles_are_exact() {
+        let merged = ObservationsStats::merge(vec![stats(1..=600), stats(601..=1000)]);
+        let whole = stats(1..=1000);
+        assert_eq!(merged.count, 1000);
+        assert_eq!((merged.min, merged.max), (1, 1000));
+        assert_eq!(merged.median, whole.median);
+        assert_eq!(merged.quantile95, whole.quantile95);
+        assert_eq!(merged.quantile99, whole.quantile99);
+    }
+
+    #[test]
+    fn merge_skips_stats_without_histogram() {
+        let merged = ObservationsStats::merge(vec![ObservationsStats::default(), stats(1..=10)]);
+        assert_eq!(merged.count, 10);
+        assert!(ObservationsStats::merge(Vec::new()).histogram.is_none());
+    }
+
+    #[test]
+    fn histogram_serialization_round_trip() {
+        let histogram = stats(1..=1000).histogram.unwrap();
+        let serialized = json::to_string(&histogram).unwrap();
+        let deserialized: Histogram = json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.0, histogram.0);
+    }
+
+    #[test]
+    fn error_rate_counts_failed_requests() {
+        let mut errors = ErrorStats {
+            delivered: 3,
+            ..Default::default()
+        };
+        errors.rpc_error("rejected".into());
+        // A delivered request, which both timed out and failed to execute
+        errors.executed_with_error += 1;
+        errors.signature_timeouts += 1;
+        errors.account_update_timeouts += 1;
+        errors.failed += 1;
+        assert_eq!(errors.error_rate(), 50.0);
+        // The results of older versions, which don't count the failed requests
+        errors.failed = 0;
+        assert_eq!(errors.error_rate(), 100.0);
     }
 }
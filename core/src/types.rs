@@ -1,5 +1,7 @@
 use std::error::Error;
+use std::f64::consts::TAU;
 use std::fmt;
+use std::time::Duration;
 
 use serde::{
     de::{self, Visitor},
@@ -33,6 +35,10 @@ pub enum BenchMode {
     /// **(TPS)** Sends commit transactions to the Ephemeral Rollup (ER) to test state-committing performance.
     #[serde(rename_all = "kebab-case")]
     Commit { accounts_per_transaction: u8 },
+    /// **(TPS)** Commits accounts to the base chain and undelegates them from the ER. The
+    /// undelegated accounts are delegated again on the base chain, and reused once they are.
+    #[serde(rename_all = "kebab-case")]
+    CommitAndUndelegate { accounts_per_transaction: u8 },
 
     /// **(RPS)** Fetches account information for a single account.
     GetAccountInfo,
@@ -43,10 +49,133 @@ pub enum BenchMode {
     /// **(RPS)** Fetches the token balance of a single token account.
     GetTokenAccountBalance,
 
+    /// **(TPS)** Packs the instructions of several TPS modes into a single transaction.
+    #[serde(rename_all = "kebab-case")]
+    Composite {
+        instructions: Vec<CompositeInstruction>,
+        #[serde(default)]
+        account_sharing: AccountSharing,
+    },
+
     /// A mixed mode that combines multiple benchmark modes with specified weights.
     Mixed(Vec<WeightedBenchMode>),
 }
 
+impl BenchMode {
+    /// Returns the name of the mode, as used in the configuration, e.g. `get-account-info`.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::SimpleByteSet { .. } => "simple-byte-set",
+            Self::HighCuCost { .. } => "high-cu-cost",
+            Self::ReadWrite { .. } => "read-write",
+            Self::ReadOnly { .. } => "read-only",
+            Self::Commit { .. } => "commit",
+            Self::CommitAndUndelegate { .. } => "commit-and-undelegate",
+            Self::GetAccountInfo => "get-account-info",
+            Self::GetMultipleAccounts => "get-multiple-accounts",
+            Self::GetBalance => "get-balance",
+            Self::GetTokenAccountBalance => "get-token-account-balance",
+            Self::Composite { .. } => "composite",
+            Self::Mixed(_) => "mixed",
+        }
+    }
+
+    /// Returns the names of the modes, whose options apply to the benchmark: the mode
+    /// itself, along with the modes combined by the mixed mode.
+    pub fn keys(&self) -> Vec<&'static str> {
+        let mut keys = vec![self.key()];
+        if let Self::Mixed(modes) = self {
+            keys.extend(modes.iter().flat_map(|m| m.mode.keys()));
+        }
+        keys
+    }
+
+    /// Tells whether the mode sends transactions, as opposed to RPC requests.
+    pub fn is_transaction(&self) -> bool {
+        matches!(
+            self,
+            Self::SimpleByteSet { .. }
+                | Self::HighCuCost { .. }
+                | Self::ReadWrite { .. }
+                | Self::ReadOnly { .. }
+                | Self::Commit { .. }
+                | Self::CommitAndUndelegate { .. }
+                | Self::Composite { .. }
+        )
+    }
+
+    /// Tells whether the mode undelegates accounts, including the modes combined
+    /// in a mixed or a composite mode.
+    pub fn undelegates(&self) -> bool {
+        match self {
+            Self::CommitAndUndelegate { .. } => true,
+            Self::Mixed(modes) => modes.iter().any(|m| m.mode.undelegates()),
+            Self::Composite { instructions, .. } => {
+                instructions.iter().any(|i| i.mode.undelegates())
+            }
+            _ => false,
+        }
+    }
+
+    /// Checks that the composite modes, including those combined in a mixed mode, are
+    /// well-formed for the given number of accounts.
+    pub fn validate(&self, accounts_count: u8) -> BenchResult<()> {
+        match self {
+            Self::Mixed(modes) => modes
+                .iter()
+                .try_for_each(|m| m.mode.validate(accounts_count)),
+            Self::Composite {
+                instructions,
+                account_sharing,
+            } => {
+                let mut slots = 0;
+                for instruction in instructions {
+                    let mode = &instruction.mode;
+                    if !mode.is_transaction() || matches!(mode, Self::Composite { .. }) {
+                        let key = mode.key();
+                        return Err(format!("composite mode can't include the {key} mode").into());
+                    }
+                    slots += instruction.count as usize;
+                }
+                if slots == 0 {
+                    return Err("composite mode should include at least one instruction".into());
+                }
+                if matches!(account_sharing, AccountSharing::Disjoint)
+                    && slots > accounts_count as usize
+                {
+                    let error = format!("disjoint composite mode needs at least {slots} accounts");
+                    return Err(error.into());
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Represents an instruction template of a composite mode, repeated in every transaction.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct CompositeInstruction {
+    /// The TPS mode, whose instruction is included in the transaction.
+    pub mode: BenchMode,
+    /// The number of instructions of the mode in every transaction.
+    pub count: u8,
+}
+
+/// Defines how the instructions of a composite transaction select their accounts.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccountSharing {
+    /// Every instruction draws from all the accounts, the accounts referenced by multiple
+    /// instructions are deduplicated in the transaction message.
+    #[default]
+    Shared,
+    /// The accounts are split into disjoint partitions, one per instruction, so that no
+    /// two instructions of a transaction touch the same account.
+    Disjoint,
+}
+
 /// Represents a benchmark mode with an assigned weight for mixed-mode benchmarks.
 #[derive(Deserialize, Serialize, Clone)]
 #[serde(rename_all = "kebab-case")]
@@ -57,6 +186,84 @@ pub struct WeightedBenchMode {
     pub weight: u16,
 }
 
+/// Defines a schedule for the target rate, allowing the load to change over the course of the benchmark.
+/// All durations and periods are specified in seconds.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum RateProfile {
+    /// Linearly ramps the rate from `from` to `to` over `duration` seconds, then holds `to`.
+    Ramp { from: u32, to: u32, duration: u64 },
+    /// Goes through a staircase of rates, holding each for its own duration.
+    /// The last step is held until the end of the benchmark.
+    Steps(Vec<RateStep>),
+    /// Keeps the rate at `base`, spiking to `peak` for the last `length` seconds of every `period`.
+    Spike {
+        base: u32,
+        peak: u32,
+        period: u64,
+        length: u64,
+    },
+    /// Oscillates the rate between `min` and `max` following a sine wave with the given `period`.
+    Sine { min: u32, max: u32, period: u64 },
+}
+
+/// A single step of the staircase rate profile.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct RateStep {
+    /// The target rate during this step.
+    pub rate: u32,
+    /// How long to hold the rate, in seconds.
+    pub duration: u64,
+}
+
+impl RateProfile {
+    /// Returns the target rate at the given point of time since the start of the benchmark.
+    pub fn rate_at(&self, elapsed: Duration) -> u32 {
+        let secs = elapsed.as_secs_f64();
+        match *self {
+            Self::Ramp { from, to, duration } => {
+                if secs >= duration as f64 {
+                    return to;
+                }
+                let progress = secs / duration as f64;
+                (from as f64 + (to as f64 - from as f64) * progress).round() as u32
+            }
+            Self::Steps(ref steps) => {
+                let mut end = 0;
+                for step in steps {
+                    end += step.duration;
+                    if elapsed.as_secs() < end {
+                        return step.rate;
+                    }
+                }
+                steps.last().map(|s| s.rate).unwrap_or_default()
+            }
+            Self::Spike {
+                base,
+                peak,
+                period,
+                length,
+            } => {
+                if period == 0 || elapsed.as_secs() % period < period.saturating_sub(length) {
+                    base
+                } else {
+                    peak
+                }
+            }
+            Self::Sine { min, max, period } => {
+                if period == 0 {
+                    return min;
+                }
+                let phase = secs / period as f64 * TAU;
+                let middle = (min as f64 + max as f64) / 2.0;
+                let amplitude = (max as f64 - min as f64) / 2.0;
+                (middle + amplitude * phase.sin()).round() as u32
+            }
+        }
+    }
+}
+
 /// Defines the type of HTTP connection to use for the benchmark.
 #[derive(Deserialize, Serialize, Clone, Copy)]
 #[serde(rename_all = "kebab-case")]
@@ -67,6 +274,53 @@ pub enum ConnectionType {
     Http2,
 }
 
+/// Defines how the ephemeral endpoint is selected for every request.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum EndpointSelection {
+    /// Cycle through the endpoints in order.
+    #[default]
+    RoundRobin,
+    /// Pick an endpoint uniformly at random.
+    Random,
+    /// Pick the endpoint with the fewest requests awaiting a response.
+    LeastInflight,
+    /// Always send the transactions of the same payer to the same endpoint.
+    StickyByPayer,
+}
+
+/// Defines how the compute unit price, i.e. the priority fee, is drawn for every transaction.
+/// The prices are in micro-lamports per compute unit.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub enum PriorityFee {
+    /// The same price for all the transactions.
+    Fixed(u64),
+    /// A price drawn uniformly at random from the range, inclusive.
+    Uniform { min: u64, max: u64 },
+    /// A price per payer, with the payers assigned to the tiers in turn.
+    Tiers(Vec<u64>),
+}
+
+/// Defines how the accounts of every request are selected, which controls the level
+/// of lock contention between the transactions.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum AccountSelection {
+    /// Every account is equally likely to be selected.
+    #[default]
+    Uniform,
+    /// The account of rank `k` is selected with a weight of `1 / k^skew`, concentrating
+    /// the load on a few hot accounts as the skew grows. A skew of 0 is uniform.
+    Zipf { skew: f64 },
+    /// Consecutive requests select a window of consecutive accounts, which slides
+    /// by one account per request.
+    Sequential,
+    /// The accounts are split into disjoint partitions, one per payer, and every payer
+    /// selects uniformly within its own partition, so different payers never contend.
+    Partitioned,
+}
+
 /// Defines the size of accounts to be used in the benchmark.
 #[derive(Deserialize, Serialize, Clone, Copy)]
 #[serde(rename_all = "lowercase")]
@@ -99,27 +353,116 @@ impl AccountEncoding {
     }
 }
 
+/// Defines the encoding of the serialized transactions in `sendTransaction` requests.
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionEncoding {
+    Base58,
+    #[default]
+    Base64,
+}
+
+impl TransactionEncoding {
+    /// Returns the string representation of the transaction encoding.
+    pub fn as_str(&self) -> &str {
+        match self {
+            TransactionEncoding::Base58 => "base58",
+            TransactionEncoding::Base64 => "base64",
+        }
+    }
+}
+
+/// Defines the format of the benchmark transactions.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransactionVersion {
+    /// Legacy transactions, which list all of their accounts in the message.
+    #[default]
+    Legacy,
+    /// Version 0 transactions, which reference the benchmark PDAs through an
+    /// address lookup table, created by `assist prepare`.
+    V0,
+}
+
+/// Defines the commitment level, at which the node reports the state and the confirmations.
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Commitment {
+    /// The block has been processed by the node, the fastest and the least durable level.
+    #[default]
+    Processed,
+    /// The block has been voted on by the supermajority of the cluster.
+    Confirmed,
+    /// The block has been rooted by the supermajority of the cluster.
+    Finalized,
+}
+
+impl Commitment {
+    /// Returns the string representation of the commitment level.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Commitment::Processed => "processed",
+            Commitment::Confirmed => "confirmed",
+            Commitment::Finalized => "finalized",
+        }
+    }
+
+    /// Parses the commitment level, as reported by the RPC, e.g. in `confirmationStatus`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "processed" => Some(Commitment::Processed),
+            "confirmed" => Some(Commitment::Confirmed),
+            "finalized" => Some(Commitment::Finalized),
+            _ => None,
+        }
+    }
+}
+
 /// A wrapper around `hyper::Uri` to provide custom methods for URL manipulation.
 #[derive(Clone)]
 pub struct Url(pub hyper::Uri);
 
 impl Url {
     /// Returns the full address string, including the host and port.
-    ///
-    /// # Arguments
-    ///
-    /// * `ws` - A boolean indicating whether to use the WebSocket port (port + 1).
-    pub fn address(&self, ws: bool) -> String {
+    pub fn address(&self) -> String {
         let host = self.host();
-        let port = self.0.port_u16().map(|p| p + ws as u16).unwrap_or(80);
+        let default = if self.is_secure() { 443 } else { 80 };
+        let port = self.0.port_u16().unwrap_or(default);
 
         format!("{}:{}", host, port)
     }
 
+    /// Derives the URL of the WebSocket endpoint, following the convention of serving
+    /// it on the same host at the next port, e.g. `http://host:8899` -> `ws://host:8900`.
+    pub fn websocket(&self) -> Url {
+        let scheme = if self.is_secure() { "wss" } else { "ws" };
+        let authority = match self.0.port_u16() {
+            Some(port) => format!("{}:{}", self.host(), port + 1),
+            None => self.host().to_owned(),
+        };
+        let uri = hyper::Uri::builder()
+            .scheme(scheme)
+            .authority(authority)
+            .path_and_query(self.path())
+            .build()
+            .expect("derived websocket uri should be valid");
+        Url(uri)
+    }
+
+    /// Returns whether the URL requires TLS, i.e. its scheme is `https` or `wss`.
+    pub fn is_secure(&self) -> bool {
+        matches!(self.0.scheme_str(), Some("https" | "wss"))
+    }
+
     /// Returns the host part of the URL.
     pub fn host(&self) -> &str {
         self.0.host().expect("uri has no host")
     }
+
+    /// Returns the path and query part of the URL, `/` if empty.
+    pub fn path(&self) -> &str {
+        self.0.path_and_query().map_or("/", |p| p.as_str())
+    }
 }
 
 impl<'de> Deserialize<'de> for Url {
@@ -150,3 +493,79 @@ impl<'de> Deserialize<'de> for Url {
         deserializer.deserialize_str(UrlVisitor)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rates(profile: &RateProfile, secs: &[u64]) -> Vec<u32> {
+        secs.iter()
+            .map(|&s| profile.rate_at(Duration::from_secs(s)))
+            .collect()
+    }
+
+    #[test]
+    fn ramp_holds_the_target_rate() {
+        let ramp = RateProfile::Ramp {
+            from: 100,
+            to: 300,
+            duration: 10,
+        };
+        assert_eq!(rates(&ramp, &[0, 5, 10, 60]), [100, 200, 300, 300]);
+    }
+
+    #[test]
+    fn steps_hold_the_last_rate() {
+        let steps = RateProfile::Steps(vec![
+            RateStep {
+                rate: 10,
+                duration: 2,
+            },
+            RateStep {
+                rate: 20,
+                duration: 3,
+            },
+        ]);
+        assert_eq!(
+            rates(&steps, &[0, 1, 2, 4, 5, 100]),
+            [10, 10, 20, 20, 20, 20]
+        );
+    }
+
+    #[test]
+    fn spike_at_the_end_of_every_period() {
+        let spike = RateProfile::Spike {
+            base: 50,
+            peak: 500,
+            period: 10,
+            length: 2,
+        };
+        assert_eq!(
+            rates(&spike, &[0, 7, 8, 9, 10, 18]),
+            [50, 50, 500, 500, 50, 500]
+        );
+    }
+
+    #[test]
+    fn sine_oscillates_between_the_bounds() {
+        let sine = RateProfile::Sine {
+            min: 100,
+            max: 200,
+            period: 4,
+        };
+        assert_eq!(rates(&sine, &[0, 1, 2, 3, 4]), [150, 200, 150, 100, 150]);
+    }
+
+    #[test]
+    fn commitment_parses_its_own_representation() {
+        for commitment in [
+            Commitment::Processed,
+            Commitment::Confirmed,
+            Commitment::Finalized,
+        ] {
+            assert!(Commitment::parse(commitment.as_str()) == Some(commitment));
+        }
+        assert!(Commitment::parse("Finalized").is_none());
+        assert!(Commitment::parse("").is_none());
+    }
+}